@@ -0,0 +1,80 @@
+use crate::state::{SaveState, StateReader, StateWriter};
+
+pub struct Envelope {
+    pub initial_volume: u8,
+    pub increase: bool,
+    pub period: u8,
+    pub volume: u8,
+    timer: u8,
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Envelope::new()
+    }
+}
+
+impl Envelope {
+    pub fn new() -> Self {
+        Envelope {
+            initial_volume: 0,
+            increase: false,
+            period: 0,
+            volume: 0,
+            timer: 0,
+        }
+    }
+
+    // NRx2: VVVV APPP
+    pub fn write(&mut self, value: u8) {
+        self.initial_volume = value >> 4;
+        self.increase = value & 0x08 > 0;
+        self.period = value & 0x07;
+    }
+
+    // the DAC is off when both the initial volume and the direction bit are 0
+    pub fn dac_enabled(&self) -> bool {
+        self.initial_volume > 0 || self.increase
+    }
+
+    pub fn trigger(&mut self) {
+        self.volume = self.initial_volume;
+        self.timer = if self.period == 0 { 8 } else { self.period };
+    }
+
+    pub fn clock(&mut self) {
+        if self.period == 0 {
+            return;
+        }
+        if self.timer > 0 {
+            self.timer -= 1;
+        }
+        if self.timer == 0 {
+            self.timer = self.period;
+            if self.increase && self.volume < 15 {
+                self.volume += 1;
+            } else if !self.increase && self.volume > 0 {
+                self.volume -= 1;
+            }
+        }
+    }
+}
+
+impl SaveState for Envelope {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.initial_volume);
+        writer.write_bool(self.increase);
+        writer.write_u8(self.period);
+        writer.write_u8(self.volume);
+        writer.write_u8(self.timer);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.initial_volume = reader.read_u8()?;
+        self.increase = reader.read_bool()?;
+        self.period = reader.read_u8()?;
+        self.volume = reader.read_u8()?;
+        self.timer = reader.read_u8()?;
+        Ok(())
+    }
+}
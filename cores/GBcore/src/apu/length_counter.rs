@@ -0,0 +1,64 @@
+use crate::state::{SaveState, StateReader, StateWriter};
+
+pub struct LengthCounter {
+    pub enabled: bool,
+    pub counter: u16,
+    max: u16,
+}
+
+impl LengthCounter {
+    pub fn new(max: u16) -> Self {
+        LengthCounter {
+            enabled: false,
+            counter: 0,
+            max,
+        }
+    }
+
+    pub fn load(&mut self, value: u16) {
+        self.counter = self.max - value;
+    }
+
+    // returns true when the counter just ran out and the channel has to stop
+    pub fn clock(&mut self) -> bool {
+        if self.enabled && self.counter > 0 {
+            self.counter -= 1;
+            return self.counter == 0;
+        }
+        false
+    }
+
+    // Enabling the counter while the frame sequencer is in the first half of
+    // a length period clocks it once more. Returns true if that made it run out.
+    pub fn set_enabled(&mut self, enabled: bool, first_half: bool) -> bool {
+        let was_enabled = self.enabled;
+        self.enabled = enabled;
+        if !was_enabled && enabled && first_half && self.counter > 0 {
+            self.counter -= 1;
+            return self.counter == 0;
+        }
+        false
+    }
+
+    pub fn trigger(&mut self, first_half: bool) {
+        if self.counter == 0 {
+            self.counter = self.max;
+            if self.enabled && first_half {
+                self.counter -= 1;
+            }
+        }
+    }
+}
+
+impl SaveState for LengthCounter {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bool(self.enabled);
+        writer.write_u16(self.counter);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.enabled = reader.read_bool()?;
+        self.counter = reader.read_u16()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,304 @@
+pub mod envelope;
+pub mod length_counter;
+pub mod noise;
+pub mod pulse;
+pub mod wave;
+
+use noise::NoiseChannel;
+use pulse::PulseChannel;
+use wave::WaveChannel;
+
+use crate::state::{SaveState, StateReader, StateWriter};
+
+// bits that always read back as 1 for FF10 - FF26
+static READ_MASKS: [u8; 23] = [
+    0x80, 0x3F, 0x00, 0xFF, 0xBF, // NR10 - NR14
+    0xFF, 0x3F, 0x00, 0xFF, 0xBF, // NR20 - NR24
+    0x7F, 0xFF, 0x9F, 0xFF, 0xBF, // NR30 - NR34
+    0xFF, 0xFF, 0x00, 0x00, 0xBF, // NR40 - NR44
+    0x00, 0x00, 0x70, // NR50 - NR52
+];
+
+const NR50: usize = 0x14;
+const NR51: usize = 0x15;
+const NR52: usize = 0x16;
+
+// M-cycles per second, the rate the mixer runs at
+const MIXER_RATE: u32 = 1_048_576;
+pub const DEFAULT_SAMPLE_RATE: u32 = 48_000;
+
+pub struct APU {
+    pub ch1: PulseChannel,
+    pub ch2: PulseChannel,
+    pub ch3: WaveChannel,
+    pub ch4: NoiseChannel,
+    regs: [u8; 0x17],
+    pub powered: bool,
+    frame_sequencer_step: u8,
+    t_cycles: u8,
+
+    sample_rate: u32,
+    sample_counter: u32,
+    left_sum: f32,
+    right_sum: f32,
+    sum_count: u32,
+    left_capacitor: f32,
+    right_capacitor: f32,
+    // interleaved left/right
+    samples: Vec<f32>,
+}
+
+impl Default for APU {
+    fn default() -> Self {
+        APU::new()
+    }
+}
+
+impl APU {
+    pub fn new() -> Self {
+        APU {
+            ch1: PulseChannel::new(true),
+            ch2: PulseChannel::new(false),
+            ch3: WaveChannel::new(),
+            ch4: NoiseChannel::new(),
+            regs: [0; 0x17],
+            powered: false,
+            frame_sequencer_step: 0,
+            t_cycles: 0,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            sample_counter: 0,
+            left_sum: 0.0,
+            right_sum: 0.0,
+            sum_count: 0,
+            left_capacitor: 0.0,
+            right_capacitor: 0.0,
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn read(&self, address: usize) -> u8 {
+        let register = address - 0xFF10;
+        match register {
+            NR52 => {
+                0x70 | ((self.powered as u8) << 7)
+                    | ((self.ch4.enabled as u8) << 3)
+                    | ((self.ch3.enabled as u8) << 2)
+                    | ((self.ch2.enabled as u8) << 1)
+                    | self.ch1.enabled as u8
+            }
+            0x00..NR52 => self.regs[register] | READ_MASKS[register],
+            0x17..0x20 => 0xFF,
+            0x20..0x30 => self.ch3.wave_ram[register - 0x20],
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, address: usize, value: u8) {
+        let register = address - 0xFF10;
+        if register >= 0x20 {
+            self.ch3.wave_ram[register - 0x20] = value;
+            return;
+        }
+        if register == NR52 {
+            self.set_power(value & 0x80 > 0);
+            return;
+        }
+        if register > NR52 {
+            return;
+        }
+
+        let first_half = self.frame_sequencer_step & 1 == 1;
+        if !self.powered {
+            // only the length counters stay writable while powered off
+            match register {
+                0x01 => self.ch1.length.load((value & 0x3F) as u16),
+                0x06 => self.ch2.length.load((value & 0x3F) as u16),
+                0x0B => self.ch3.length.load(value as u16),
+                0x10 => self.ch4.length.load((value & 0x3F) as u16),
+                _ => {}
+            }
+            return;
+        }
+
+        self.regs[register] = value;
+        match register {
+            0x00..0x05 => self.ch1.write(register, value, first_half),
+            0x05..0x0A => self.ch2.write(register - 0x05, value, first_half),
+            0x0A..0x0F => self.ch3.write(register - 0x0A, value, first_half),
+            0x0F..0x14 => self.ch4.write(register - 0x0F, value, first_half),
+            _ => {}
+        }
+    }
+
+    // Stores a register as it is, nothing gets triggered or powered down
+    pub fn poke(&mut self, address: usize, value: u8) {
+        let register = address - 0xFF10;
+        match register {
+            NR52 => self.powered = value & 0x80 > 0,
+            0x00..NR52 => self.regs[register] = value,
+            0x20..0x30 => self.ch3.wave_ram[register - 0x20] = value,
+            _ => {}
+        }
+    }
+
+    fn set_power(&mut self, on: bool) {
+        if on && !self.powered {
+            self.frame_sequencer_step = 0;
+        }
+        if !on && self.powered {
+            self.regs = [0; 0x17];
+            self.ch1.power_off();
+            self.ch2.power_off();
+            self.ch3.power_off();
+            self.ch4.power_off();
+        }
+        self.powered = on;
+    }
+
+    // one T-cycle
+    pub fn tick(&mut self) {
+        if self.powered {
+            self.ch1.tick();
+            self.ch2.tick();
+            self.ch3.tick();
+            self.ch4.tick();
+        }
+
+        self.t_cycles += 1;
+        if self.t_cycles == 4 {
+            self.t_cycles = 0;
+            self.mix();
+        }
+    }
+
+    // clocked at 512Hz by the falling edge of DIV bit 4
+    pub fn frame_sequencer_tick(&mut self) {
+        if !self.powered {
+            return;
+        }
+        match self.frame_sequencer_step {
+            0 | 4 => self.clock_length(),
+            2 | 6 => {
+                self.clock_length();
+                self.ch1.clock_sweep();
+            }
+            7 => {
+                self.ch1.clock_envelope();
+                self.ch2.clock_envelope();
+                self.ch4.clock_envelope();
+            }
+            _ => {}
+        }
+        self.frame_sequencer_step = (self.frame_sequencer_step + 1) & 7;
+    }
+
+    fn clock_length(&mut self) {
+        self.ch1.clock_length();
+        self.ch2.clock_length();
+        self.ch3.clock_length();
+        self.ch4.clock_length();
+    }
+
+    fn mix(&mut self) {
+        let dac = |enabled: bool, output: u8| {
+            if enabled {
+                output as f32 / 7.5 - 1.0
+            } else {
+                0.0
+            }
+        };
+        let outputs = [
+            dac(self.ch1.dac_enabled(), self.ch1.output()),
+            dac(self.ch2.dac_enabled(), self.ch2.output()),
+            dac(self.ch3.dac_enabled, self.ch3.output()),
+            dac(self.ch4.dac_enabled(), self.ch4.output()),
+        ];
+
+        let panning = self.regs[NR51];
+        let mut left = 0.0;
+        let mut right = 0.0;
+        for (i, output) in outputs.iter().enumerate() {
+            if panning & (0x10 << i) > 0 {
+                left += output;
+            }
+            if panning & (0x01 << i) > 0 {
+                right += output;
+            }
+        }
+        let volume = self.regs[NR50];
+        left *= (((volume >> 4) & 0x07) + 1) as f32 / 8.0 / 4.0;
+        right *= ((volume & 0x07) + 1) as f32 / 8.0 / 4.0;
+
+        self.left_sum += left;
+        self.right_sum += right;
+        self.sum_count += 1;
+
+        self.sample_counter += self.sample_rate;
+        if self.sample_counter >= MIXER_RATE {
+            self.sample_counter -= MIXER_RATE;
+            self.push_sample();
+        }
+    }
+
+    // averages everything mixed since the last output sample and removes the DC offset
+    fn push_sample(&mut self) {
+        let count = self.sum_count.max(1) as f32;
+        let left = self.left_sum / count;
+        let right = self.right_sum / count;
+        self.left_sum = 0.0;
+        self.right_sum = 0.0;
+        self.sum_count = 0;
+
+        let charge = 0.999958_f32.powf(4_194_304.0 / self.sample_rate as f32);
+        let left_out = left - self.left_capacitor;
+        self.left_capacitor = left - left_out * charge;
+        let right_out = right - self.right_capacitor;
+        self.right_capacitor = right - right_out * charge;
+
+        // nobody is draining the samples, keep at most one second around
+        if self.samples.len() >= self.sample_rate as usize * 2 {
+            self.samples.drain(..self.sample_rate as usize);
+        }
+        self.samples.push(left_out);
+        self.samples.push(right_out);
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    // can be changed on the fly, frontends nudge it to keep their audio queue filled
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate.clamp(1, MIXER_RATE);
+    }
+
+    // interleaved stereo samples produced since the last call
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.samples)
+    }
+}
+
+impl SaveState for APU {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bytes(&self.regs);
+        writer.write_bool(self.powered);
+        writer.write_u8(self.frame_sequencer_step);
+        writer.write_u8(self.t_cycles);
+        self.ch1.save_state(writer);
+        self.ch2.save_state(writer);
+        self.ch3.save_state(writer);
+        self.ch4.save_state(writer);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        reader.read_bytes(&mut self.regs)?;
+        self.powered = reader.read_bool()?;
+        self.frame_sequencer_step = reader.read_u8()?;
+        self.t_cycles = reader.read_u8()?;
+        self.ch1.load_state(reader)?;
+        self.ch2.load_state(reader)?;
+        self.ch3.load_state(reader)?;
+        self.ch4.load_state(reader)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+use super::{envelope::Envelope, length_counter::LengthCounter};
+use crate::state::{SaveState, StateReader, StateWriter};
+
+static DIVISORS: [u16; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+
+// Channel 4, pseudo random noise from a linear feedback shift register
+pub struct NoiseChannel {
+    pub enabled: bool,
+    pub clock_shift: u8,
+    pub width_mode: bool,
+    pub divisor_code: u8,
+    timer: u32,
+    lfsr: u16,
+    pub length: LengthCounter,
+    pub envelope: Envelope,
+}
+
+impl Default for NoiseChannel {
+    fn default() -> Self {
+        NoiseChannel::new()
+    }
+}
+
+impl NoiseChannel {
+    pub fn new() -> Self {
+        NoiseChannel {
+            enabled: false,
+            clock_shift: 0,
+            width_mode: false,
+            divisor_code: 0,
+            timer: 0,
+            lfsr: 0x7FFF,
+            length: LengthCounter::new(64),
+            envelope: Envelope::new(),
+        }
+    }
+
+    // register is the offset from NR40 (which does not exist)
+    pub fn write(&mut self, register: usize, value: u8, first_half: bool) {
+        match register {
+            1 => self.length.load((value & 0x3F) as u16),
+            2 => {
+                self.envelope.write(value);
+                if !self.envelope.dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            3 => {
+                self.clock_shift = value >> 4;
+                self.width_mode = value & 0x08 > 0;
+                self.divisor_code = value & 0x07;
+            }
+            4 => {
+                if self.length.set_enabled(value & 0x40 > 0, first_half) && value & 0x80 == 0 {
+                    self.enabled = false;
+                }
+                if value & 0x80 > 0 {
+                    self.trigger(first_half);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn period(&self) -> u32 {
+        (DIVISORS[self.divisor_code as usize] as u32) << self.clock_shift
+    }
+
+    fn trigger(&mut self, first_half: bool) {
+        self.enabled = true;
+        self.length.trigger(first_half);
+        self.timer = self.period();
+        self.lfsr = 0x7FFF;
+        self.envelope.trigger();
+        if !self.envelope.dac_enabled() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn tick(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+        }
+        if self.timer == 0 {
+            self.timer = self.period();
+            // shift 14 and 15 stop the LFSR
+            if self.clock_shift >= 14 {
+                return;
+            }
+            let bit = (self.lfsr & 1) ^ ((self.lfsr >> 1) & 1);
+            self.lfsr = (self.lfsr >> 1) | (bit << 14);
+            if self.width_mode {
+                self.lfsr = (self.lfsr & !0x40) | (bit << 6);
+            }
+        }
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub fn dac_enabled(&self) -> bool {
+        self.envelope.dac_enabled()
+    }
+
+    pub fn output(&self) -> u8 {
+        if !self.enabled || self.lfsr & 1 > 0 {
+            return 0;
+        }
+        self.envelope.volume
+    }
+
+    pub fn power_off(&mut self) {
+        let length = self.length.counter;
+        *self = NoiseChannel::new();
+        self.length.counter = length;
+    }
+}
+
+impl SaveState for NoiseChannel {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bool(self.enabled);
+        writer.write_u8(self.clock_shift);
+        writer.write_bool(self.width_mode);
+        writer.write_u8(self.divisor_code);
+        writer.write_u32(self.timer);
+        writer.write_u16(self.lfsr);
+        self.length.save_state(writer);
+        self.envelope.save_state(writer);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.enabled = reader.read_bool()?;
+        self.clock_shift = reader.read_u8()?;
+        self.width_mode = reader.read_bool()?;
+        self.divisor_code = reader.read_u8()?;
+        self.timer = reader.read_u32()?;
+        self.lfsr = reader.read_u16()?;
+        self.length.load_state(reader)?;
+        self.envelope.load_state(reader)?;
+        Ok(())
+    }
+}
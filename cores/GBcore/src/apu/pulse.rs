@@ -0,0 +1,229 @@
+use super::{envelope::Envelope, length_counter::LengthCounter};
+use crate::state::{SaveState, StateReader, StateWriter};
+
+static DUTY_PATTERNS: [[u8; 8]; 4] = [
+    [0, 0, 0, 0, 0, 0, 0, 1], // 12.5%
+    [1, 0, 0, 0, 0, 0, 0, 1], // 25%
+    [1, 0, 0, 0, 0, 1, 1, 1], // 50%
+    [0, 1, 1, 1, 1, 1, 1, 0], // 75%
+];
+
+// Channels 1 and 2. Only channel 1 has the frequency sweep unit.
+pub struct PulseChannel {
+    pub enabled: bool,
+    has_sweep: bool,
+    pub duty: u8,
+    duty_position: u8,
+    pub frequency: u16,
+    timer: u16,
+    pub length: LengthCounter,
+    pub envelope: Envelope,
+
+    sweep_period: u8,
+    sweep_negate: bool,
+    sweep_shift: u8,
+    sweep_timer: u8,
+    sweep_enabled: bool,
+    sweep_negate_used: bool,
+    shadow_frequency: u16,
+}
+
+impl PulseChannel {
+    pub fn new(has_sweep: bool) -> Self {
+        PulseChannel {
+            enabled: false,
+            has_sweep,
+            duty: 0,
+            duty_position: 0,
+            frequency: 0,
+            timer: 0,
+            length: LengthCounter::new(64),
+            envelope: Envelope::new(),
+            sweep_period: 0,
+            sweep_negate: false,
+            sweep_shift: 0,
+            sweep_timer: 0,
+            sweep_enabled: false,
+            sweep_negate_used: false,
+            shadow_frequency: 0,
+        }
+    }
+
+    // register is the offset from NRx0
+    pub fn write(&mut self, register: usize, value: u8, first_half: bool) {
+        match register {
+            0 => {
+                if !self.has_sweep {
+                    return;
+                }
+                self.sweep_period = (value >> 4) & 0x07;
+                let negate = value & 0x08 > 0;
+                // leaving negate mode after a negated calculation kills the channel
+                if self.sweep_negate && !negate && self.sweep_negate_used {
+                    self.enabled = false;
+                }
+                self.sweep_negate = negate;
+                self.sweep_shift = value & 0x07;
+            }
+            1 => {
+                self.duty = value >> 6;
+                self.length.load((value & 0x3F) as u16);
+            }
+            2 => {
+                self.envelope.write(value);
+                if !self.envelope.dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            3 => self.frequency = (self.frequency & 0x700) | value as u16,
+            4 => {
+                self.frequency = (self.frequency & 0xFF) | (((value & 0x07) as u16) << 8);
+                if self.length.set_enabled(value & 0x40 > 0, first_half) && value & 0x80 == 0 {
+                    self.enabled = false;
+                }
+                if value & 0x80 > 0 {
+                    self.trigger(first_half);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn trigger(&mut self, first_half: bool) {
+        self.enabled = true;
+        self.length.trigger(first_half);
+        self.timer = (2048 - self.frequency) * 4;
+        self.envelope.trigger();
+
+        if self.has_sweep {
+            self.shadow_frequency = self.frequency;
+            self.sweep_timer = if self.sweep_period == 0 {
+                8
+            } else {
+                self.sweep_period
+            };
+            self.sweep_enabled = self.sweep_period > 0 || self.sweep_shift > 0;
+            self.sweep_negate_used = false;
+            if self.sweep_shift > 0 {
+                self.sweep_calculate();
+            }
+        }
+
+        if !self.envelope.dac_enabled() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn tick(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+        }
+        if self.timer == 0 {
+            self.timer = (2048 - self.frequency) * 4;
+            self.duty_position = (self.duty_position + 1) & 7;
+        }
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub fn clock_sweep(&mut self) {
+        if self.sweep_timer > 0 {
+            self.sweep_timer -= 1;
+        }
+        if self.sweep_timer > 0 {
+            return;
+        }
+        self.sweep_timer = if self.sweep_period == 0 {
+            8
+        } else {
+            self.sweep_period
+        };
+        if self.sweep_enabled && self.sweep_period > 0 {
+            let frequency = self.sweep_calculate();
+            if frequency <= 2047 && self.sweep_shift > 0 {
+                self.shadow_frequency = frequency;
+                self.frequency = frequency;
+                // the new frequency is checked for overflow again but not applied
+                self.sweep_calculate();
+            }
+        }
+    }
+
+    fn sweep_calculate(&mut self) -> u16 {
+        let delta = self.shadow_frequency >> self.sweep_shift;
+        let frequency = if self.sweep_negate {
+            self.sweep_negate_used = true;
+            self.shadow_frequency.wrapping_sub(delta)
+        } else {
+            self.shadow_frequency + delta
+        };
+        if frequency > 2047 {
+            self.enabled = false;
+        }
+        frequency
+    }
+
+    pub fn dac_enabled(&self) -> bool {
+        self.envelope.dac_enabled()
+    }
+
+    // current digital output, 0..=15
+    pub fn output(&self) -> u8 {
+        if !self.enabled {
+            return 0;
+        }
+        DUTY_PATTERNS[self.duty as usize][self.duty_position as usize] * self.envelope.volume
+    }
+
+    // APU power off clears everything but the length counter
+    pub fn power_off(&mut self) {
+        let length = self.length.counter;
+        *self = PulseChannel::new(self.has_sweep);
+        self.length.counter = length;
+    }
+}
+
+impl SaveState for PulseChannel {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bool(self.enabled);
+        writer.write_u8(self.duty);
+        writer.write_u8(self.duty_position);
+        writer.write_u16(self.frequency);
+        writer.write_u16(self.timer);
+        self.length.save_state(writer);
+        self.envelope.save_state(writer);
+        writer.write_u8(self.sweep_period);
+        writer.write_bool(self.sweep_negate);
+        writer.write_u8(self.sweep_shift);
+        writer.write_u8(self.sweep_timer);
+        writer.write_bool(self.sweep_enabled);
+        writer.write_bool(self.sweep_negate_used);
+        writer.write_u16(self.shadow_frequency);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.enabled = reader.read_bool()?;
+        self.duty = reader.read_u8()?;
+        self.duty_position = reader.read_u8()?;
+        self.frequency = reader.read_u16()?;
+        self.timer = reader.read_u16()?;
+        self.length.load_state(reader)?;
+        self.envelope.load_state(reader)?;
+        self.sweep_period = reader.read_u8()?;
+        self.sweep_negate = reader.read_bool()?;
+        self.sweep_shift = reader.read_u8()?;
+        self.sweep_timer = reader.read_u8()?;
+        self.sweep_enabled = reader.read_bool()?;
+        self.sweep_negate_used = reader.read_bool()?;
+        self.shadow_frequency = reader.read_u16()?;
+        Ok(())
+    }
+}
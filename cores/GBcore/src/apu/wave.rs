@@ -0,0 +1,137 @@
+use super::length_counter::LengthCounter;
+use crate::state::{SaveState, StateReader, StateWriter};
+
+// Channel 3, plays back 32 4-bit samples from wave RAM
+pub struct WaveChannel {
+    pub enabled: bool,
+    pub dac_enabled: bool,
+    pub volume_code: u8,
+    pub frequency: u16,
+    timer: u16,
+    position: u8,
+    sample_buffer: u8,
+    pub length: LengthCounter,
+    pub wave_ram: [u8; 16],
+}
+
+impl Default for WaveChannel {
+    fn default() -> Self {
+        WaveChannel::new()
+    }
+}
+
+impl WaveChannel {
+    pub fn new() -> Self {
+        WaveChannel {
+            enabled: false,
+            dac_enabled: false,
+            volume_code: 0,
+            frequency: 0,
+            timer: 0,
+            position: 0,
+            sample_buffer: 0,
+            length: LengthCounter::new(256),
+            wave_ram: [0; 16],
+        }
+    }
+
+    // register is the offset from NR30
+    pub fn write(&mut self, register: usize, value: u8, first_half: bool) {
+        match register {
+            0 => {
+                self.dac_enabled = value & 0x80 > 0;
+                if !self.dac_enabled {
+                    self.enabled = false;
+                }
+            }
+            1 => self.length.load(value as u16),
+            2 => self.volume_code = (value >> 5) & 0x03,
+            3 => self.frequency = (self.frequency & 0x700) | value as u16,
+            4 => {
+                self.frequency = (self.frequency & 0xFF) | (((value & 0x07) as u16) << 8);
+                if self.length.set_enabled(value & 0x40 > 0, first_half) && value & 0x80 == 0 {
+                    self.enabled = false;
+                }
+                if value & 0x80 > 0 {
+                    self.trigger(first_half);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn trigger(&mut self, first_half: bool) {
+        self.enabled = self.dac_enabled;
+        self.length.trigger(first_half);
+        // the first sample is only fetched after one extra period
+        self.timer = (2048 - self.frequency) * 2 + 6;
+        self.position = 0;
+    }
+
+    pub fn tick(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+        }
+        if self.timer == 0 {
+            self.timer = (2048 - self.frequency) * 2;
+            self.position = (self.position + 1) & 31;
+            let byte = self.wave_ram[(self.position / 2) as usize];
+            self.sample_buffer = if self.position & 1 == 0 {
+                byte >> 4
+            } else {
+                byte & 0x0F
+            };
+        }
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if !self.enabled {
+            return 0;
+        }
+        match self.volume_code {
+            0 => 0,
+            code => self.sample_buffer >> (code - 1),
+        }
+    }
+
+    pub fn power_off(&mut self) {
+        let length = self.length.counter;
+        let wave_ram = self.wave_ram;
+        *self = WaveChannel::new();
+        self.length.counter = length;
+        self.wave_ram = wave_ram;
+    }
+}
+
+impl SaveState for WaveChannel {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bool(self.enabled);
+        writer.write_bool(self.dac_enabled);
+        writer.write_u8(self.volume_code);
+        writer.write_u16(self.frequency);
+        writer.write_u16(self.timer);
+        writer.write_u8(self.position);
+        writer.write_u8(self.sample_buffer);
+        self.length.save_state(writer);
+        writer.write_bytes(&self.wave_ram);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.enabled = reader.read_bool()?;
+        self.dac_enabled = reader.read_bool()?;
+        self.volume_code = reader.read_u8()?;
+        self.frequency = reader.read_u16()?;
+        self.timer = reader.read_u16()?;
+        self.position = reader.read_u8()?;
+        self.sample_buffer = reader.read_u8()?;
+        self.length.load_state(reader)?;
+        reader.read_bytes(&mut self.wave_ram)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+use sdl2::audio::{AudioQueue, AudioSpecDesired};
+use sdl2::AudioSubsystem;
+use std::thread;
+use std::time::Duration;
+
+pub static DEVICE_RATE: i32 = 48_000;
+// the core's output rate is bent by at most this much to steer the queue
+pub static MAX_RATE_DELTA: f64 = 0.005;
+
+// Plays the APU samples through an SDL audio queue. The core does the
+// resampling from its own clock, so all that is left here is picking the
+// rate it should produce at: a bit faster when the queue runs low and a bit
+// slower when it fills up, which keeps the latency around the target.
+pub struct AudioOutput {
+    queue: AudioQueue<f32>,
+    target_latency: Duration,
+}
+
+impl AudioOutput {
+    pub fn new(subsystem: &AudioSubsystem, target_latency: Duration) -> Result<Self, String> {
+        let desired = AudioSpecDesired {
+            freq: Some(DEVICE_RATE),
+            channels: Some(2),
+            samples: Some(512),
+        };
+        let queue = subsystem.open_queue::<f32, _>(None, &desired)?;
+        queue.resume();
+        Ok(AudioOutput {
+            queue,
+            target_latency,
+        })
+    }
+
+    pub fn device_rate(&self) -> u32 {
+        self.queue.spec().freq as u32
+    }
+
+    fn target_frames(&self) -> f64 {
+        self.target_latency.as_secs_f64() * self.device_rate() as f64
+    }
+
+    // stereo frames waiting to be played
+    fn queued_frames(&self) -> f64 {
+        self.queue.size() as f64 / (std::mem::size_of::<f32>() * 2) as f64
+    }
+
+    pub fn queued(&self) -> Duration {
+        Duration::from_secs_f64(self.queued_frames() / self.device_rate() as f64)
+    }
+
+    pub fn push(&mut self, samples: &[f32]) {
+        // way behind after a hiccup, better to drop it than to lag forever
+        if self.queued_frames() > self.target_frames() * 4.0 {
+            self.queue.clear();
+        }
+        if let Err(err) = self.queue.queue_audio(samples) {
+            println!("Audio error: {}", err);
+        }
+    }
+
+    // the sample rate the core should produce for the next frame
+    pub fn adjusted_rate(&self) -> u32 {
+        let target = self.target_frames();
+        let fill = ((self.queued_frames() - target) / target).clamp(-1.0, 1.0);
+        (self.device_rate() as f64 * (1.0 - MAX_RATE_DELTA * fill)).round() as u32
+    }
+
+    // blocks until the queue drains down to the target latency
+    pub fn wait(&self) {
+        while self.queued() > self.target_latency {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    pub fn clear(&self) {
+        self.queue.clear();
+    }
+}
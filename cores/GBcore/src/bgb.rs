@@ -0,0 +1,375 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use crate::io::serial::LinkDevice;
+
+// BGB link protocol 1.4: fixed 8 byte packets (command, three data bytes and a
+// little-endian timestamp). Timestamps count a 2MiHz clock in 31 bits.
+const CMD_VERSION: u8 = 1;
+const CMD_JOYPAD: u8 = 101;
+const CMD_SYNC1: u8 = 104;
+const CMD_SYNC2: u8 = 105;
+const CMD_SYNC3: u8 = 106;
+const CMD_STATUS: u8 = 108;
+const CMD_WANT_DISCONNECT: u8 = 109;
+
+const STATUS_RUNNING: u8 = 0x01;
+const TIMESTAMP_MASK: u32 = 0x7FFF_FFFF;
+
+// all in 2MiHz clocks
+// how often the socket is looked at, about a scanline
+const POLL_INTERVAL: u64 = 228;
+// how often our time is sent over when nothing else is, about 1ms
+const SYNC_INTERVAL: u64 = 2048;
+// how far we may run ahead of the last time we heard of the peer
+const MAX_DRIFT: u64 = 8 * SYNC_INTERVAL;
+
+// Which side opens the connection
+#[derive(Debug, Clone)]
+pub enum LinkTarget {
+    Listen(String),
+    Connect(String),
+}
+
+#[derive(Clone, Copy)]
+struct Packet {
+    command: u8,
+    b2: u8,
+    b3: u8,
+    b4: u8,
+    timestamp: u32,
+}
+
+impl Packet {
+    fn new(command: u8, b2: u8, b3: u8, b4: u8, timestamp: u32) -> Self {
+        Packet {
+            command,
+            b2,
+            b3,
+            b4,
+            timestamp,
+        }
+    }
+
+    fn to_bytes(self) -> [u8; 8] {
+        let mut bytes = [self.command, self.b2, self.b3, self.b4, 0, 0, 0, 0];
+        bytes[4..].copy_from_slice(&self.timestamp.to_le_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8; 8]) -> Self {
+        Packet::new(
+            bytes[0],
+            bytes[1],
+            bytes[2],
+            bytes[3],
+            u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+        )
+    }
+}
+
+// Link cable to another emulator speaking the BGB protocol over TCP. The
+// master announces a transfer with sync1 and gets the slave's byte back with
+// sync2. Both sides keep sending their time, and a side that runs too far
+// ahead of the other waits, which bounds the drift between the two.
+pub struct BgbLink {
+    stream: Option<TcpStream>,
+    // our time in 2MiHz clocks, counted in 4MHz T-cycles
+    cycles: u64,
+    next_poll: u64,
+    next_sync: u64,
+    // last peer timestamp and where it falls on our own timeline
+    peer_clock: Option<(u32, u64)>,
+    // a sync1 that hasn't been answered yet: data and when it was sent
+    pending: Option<(u8, u64)>,
+    // the reply to our own sync1
+    reply: Option<u8>,
+    // whether we waited on the external clock since the last advance
+    listening: bool,
+    // why the connection went away
+    error: Option<String>,
+}
+
+impl BgbLink {
+    pub fn open(target: &LinkTarget) -> Result<Self, String> {
+        let stream = match target {
+            // blocks until the peer shows up
+            LinkTarget::Listen(address) => {
+                let listener = TcpListener::bind(address).map_err(|err| err.to_string())?;
+                listener.accept().map_err(|err| err.to_string())?.0
+            }
+            LinkTarget::Connect(address) => {
+                TcpStream::connect(address).map_err(|err| err.to_string())?
+            }
+        };
+        BgbLink::with_stream(stream)
+    }
+
+    pub fn with_stream(stream: TcpStream) -> Result<Self, String> {
+        stream.set_nodelay(true).map_err(|err| err.to_string())?;
+        let mut link = BgbLink {
+            stream: Some(stream),
+            cycles: 0,
+            next_poll: 0,
+            next_sync: 0,
+            peer_clock: None,
+            pending: None,
+            reply: None,
+            listening: false,
+            error: None,
+        };
+        link.send(Packet::new(CMD_VERSION, 1, 4, 0, 0));
+        match link.receive(true) {
+            Some(packet) if packet.command == CMD_VERSION => {
+                if (packet.b2, packet.b3, packet.b4) != (1, 4, 0) {
+                    return Err(format!(
+                        "Unsupported BGB link protocol {}.{}.{}",
+                        packet.b2, packet.b3, packet.b4
+                    ));
+                }
+            }
+            _ => return Err("The peer didn't answer with a BGB version packet".to_string()),
+        }
+        link.send(Packet::new(CMD_STATUS, STATUS_RUNNING, 0, 0, 0));
+        link.send_time();
+        Ok(link)
+    }
+
+    pub fn connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    pub fn peer_address(&self) -> Option<String> {
+        let stream = self.stream.as_ref()?;
+        stream.peer_addr().ok().map(|address| address.to_string())
+    }
+
+    fn disconnect(&mut self, reason: String) {
+        self.stream = None;
+        self.error = Some(reason);
+    }
+
+    fn now(&self) -> u64 {
+        self.cycles / 2
+    }
+
+    fn timestamp(&self) -> u32 {
+        self.now() as u32 & TIMESTAMP_MASK
+    }
+
+    fn send(&mut self, packet: Packet) {
+        if let Some(stream) = &mut self.stream {
+            if let Err(err) = stream.write_all(&packet.to_bytes()) {
+                self.disconnect(format!("Link error: {}", err));
+            }
+        }
+    }
+
+    fn send_time(&mut self) {
+        self.send(Packet::new(CMD_SYNC3, 0, 0, 0, self.timestamp()));
+        self.next_sync = self.now() + SYNC_INTERVAL;
+    }
+
+    // None when nothing is there yet or the connection is gone
+    fn receive(&mut self, blocking: bool) -> Option<Packet> {
+        let stream = self.stream.as_mut()?;
+        let mut bytes = [0; 8];
+        if !blocking {
+            stream.set_nonblocking(true).ok()?;
+            let mut peek = [0; 8];
+            let available = stream.peek(&mut peek);
+            stream.set_nonblocking(false).ok()?;
+            match available {
+                Ok(count) if count == bytes.len() => {}
+                // a closed connection shows up as a short read below
+                Ok(0) => {}
+                Ok(_) => return None,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return None,
+                Err(_) => {}
+            }
+        }
+        match stream.read_exact(&mut bytes) {
+            Ok(()) => Some(Packet::from_bytes(&bytes)),
+            Err(err) => {
+                self.disconnect(format!("Link closed: {}", err));
+                None
+            }
+        }
+    }
+
+    // extends a 31 bit peer timestamp onto our own timeline
+    fn peer_time(&mut self, timestamp: u32) -> u64 {
+        let local = match self.peer_clock {
+            None => self.now(),
+            Some((last_timestamp, last_local)) => {
+                let delta = timestamp.wrapping_sub(last_timestamp) & TIMESTAMP_MASK;
+                // anything further than half the range is a stale packet
+                if delta > TIMESTAMP_MASK / 2 {
+                    return last_local;
+                }
+                last_local + delta as u64
+            }
+        };
+        self.peer_clock = Some((timestamp, local));
+        local
+    }
+
+    fn handle(&mut self, packet: Packet) {
+        match packet.command {
+            CMD_SYNC1 => {
+                let sent = self.peer_time(packet.timestamp);
+                self.pending = Some((packet.b2, sent));
+            }
+            CMD_SYNC2 => self.reply = Some(packet.b2),
+            CMD_SYNC3 => {
+                if packet.b2 == 1 {
+                    // the peer wasn't listening
+                    self.reply = Some(0xFF);
+                } else {
+                    self.peer_time(packet.timestamp);
+                }
+            }
+            CMD_WANT_DISCONNECT => self.disconnect("The peer disconnected".to_string()),
+            // joypad, status and versions sent again don't matter for the link itself
+            CMD_JOYPAD | CMD_STATUS | CMD_VERSION => {}
+            _ => {}
+        }
+    }
+
+    fn poll(&mut self) {
+        while let Some(packet) = self.receive(false) {
+            self.handle(packet);
+        }
+    }
+}
+
+impl LinkDevice for BgbLink {
+    fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    fn exchange(&mut self, data: u8) -> u8 {
+        self.reply = None;
+        self.send(Packet::new(CMD_SYNC1, data, 0x81, 0, self.timestamp()));
+        self.next_sync = self.now() + SYNC_INTERVAL;
+        while self.reply.is_none() {
+            match self.receive(true) {
+                Some(packet) => self.handle(packet),
+                None => return 0xFF,
+            }
+            // both sides drive the clock, neither listens
+            if self.pending.take().is_some() {
+                self.send(Packet::new(CMD_SYNC3, 1, 0, 0, 0));
+            }
+        }
+        self.reply.take().unwrap_or(0xFF)
+    }
+
+    fn clock_in(&mut self, data: u8) -> Option<u8> {
+        self.listening = true;
+        match self.pending {
+            Some((incoming, sent)) if self.now() >= sent => {
+                self.pending = None;
+                self.send(Packet::new(CMD_SYNC2, data, 0x80, 0, 0));
+                Some(incoming)
+            }
+            _ => None,
+        }
+    }
+
+    fn advance(&mut self, elapsed: u32) {
+        self.cycles += elapsed as u64;
+        let listened = std::mem::take(&mut self.listening);
+        let now = self.now();
+        if now < self.next_poll || !self.connected() {
+            return;
+        }
+        self.next_poll = now + POLL_INTERVAL;
+        if now >= self.next_sync {
+            self.send_time();
+        }
+        self.poll();
+
+        // a transfer we weren't ready for when it was clocked
+        if let Some((_, sent)) = self.pending {
+            if now >= sent && !listened {
+                self.pending = None;
+                self.send(Packet::new(CMD_SYNC3, 1, 0, 0, 0));
+            }
+        }
+
+        // too far ahead, hold the machine until the peer catches up
+        let mut waited = false;
+        while let Some((_, peer)) = self.peer_clock {
+            if now <= peer + MAX_DRIFT || !self.connected() {
+                break;
+            }
+            if !waited {
+                self.send_time();
+                waited = true;
+            }
+            match self.receive(true) {
+                Some(packet) => self.handle(packet),
+                None => break,
+            }
+            // the peer waits for our answer, it won't move on before it has it
+            if self.pending.is_some() {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    fn listen() -> (TcpListener, String) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        (listener, address)
+    }
+
+    #[test]
+    fn handshake_and_exchange() {
+        let (listener, address) = listen();
+        let slave = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut link = BgbLink::with_stream(stream).unwrap();
+            // a scanline at a time until the master's byte comes in
+            loop {
+                if let Some(data) = link.clock_in(0x99) {
+                    return data;
+                }
+                assert!(link.connected());
+                link.advance(456);
+            }
+        });
+
+        let mut master = BgbLink::with_stream(TcpStream::connect(address).unwrap()).unwrap();
+        assert!(master.connected());
+        assert_eq!(master.exchange(0x42), 0x99);
+        assert_eq!(slave.join().unwrap(), 0x42);
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let (listener, address) = listen();
+        let peer = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream
+                .write_all(&Packet::new(CMD_VERSION, 1, 3, 0, 0).to_bytes())
+                .unwrap();
+            let mut version = [0; 8];
+            stream.read_exact(&mut version).unwrap();
+            version
+        });
+
+        let link = BgbLink::with_stream(TcpStream::connect(address).unwrap());
+        assert!(link.is_err_and(|err| err.contains("1.3.0")));
+        // ours went out all the same
+        assert_eq!(peer.join().unwrap()[..4], [CMD_VERSION, 1, 4, 0]);
+    }
+}
@@ -1,15 +1,50 @@
-use super::{cartridge, io::IOManager, ppu::PPU, timer::Timer};
+use super::{
+    apu::APU,
+    cartridge,
+    cheats::RamWrite,
+    debugger::{WatchHit, Watchpoint},
+    dma::HDMA,
+    io::IOManager,
+    ppu::PPU,
+    state::{SaveState, StateReader, StateWriter},
+    timer::Timer,
+};
 
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
 
 pub struct Bus {
     pub cart: Option<cartridge::Cartridge>,
     pub ppu: PPU,
     pub timer: Timer,
-    pub wram: [u8; 0x2000],
+    pub apu: APU,
+    // 8 banks of 4KB, banks 2-7 are only reachable on CGB
+    pub wram: [u8; 0x8000],
+    pub wram_bank: usize,
     pub hram: [u8; 0x80],
     pub ioram: Rc<RefCell<IOManager>>,
     pub ie_register: u8,
+
+    // CGB registers are only visible when a CGB runs a CGB cartridge
+    pub cgb_mode: bool,
+    pub double_speed: bool,
+    pub speed_switch_armed: bool,
+    pub hdma: HDMA,
+    // mapped over the cartridge until something is written to FF50
+    pub boot_rom: Vec<u8>,
+    pub boot_rom_mapped: bool,
+    // M-cycles the CPU has to sit out while VRAM DMA runs
+    pub hdma_stall: u32,
+    // LY reads this instead when set, Gameboy Doctor logs expect 0x90
+    pub ly_override: Option<u8>,
+    // debugger watchpoints, accesses are only checked while there are any
+    pub watchpoints: Vec<Watchpoint>,
+    // the first watched access since the debugger last looked
+    pub watch_hit: Cell<Option<WatchHit>>,
+    // enabled GameShark codes
+    pub ram_cheats: Vec<RamWrite>,
 }
 
 impl Bus {
@@ -17,14 +52,45 @@ impl Bus {
         Bus {
             cart: None,
             timer: Timer::new(),
+            apu: APU::new(),
             ppu: PPU::new(),
-            wram: [0; 0x2000],
+            wram: [0; 0x8000],
+            wram_bank: 1,
             hram: [0; 0x80],
             ioram: Rc::new(RefCell::new(IOManager::new())),
             ie_register: 0,
+            cgb_mode: false,
+            double_speed: false,
+            speed_switch_armed: false,
+            hdma: HDMA::new(),
+            boot_rom: Vec::new(),
+            boot_rom_mapped: false,
+            hdma_stall: 0,
+            ly_override: None,
+            watchpoints: Vec::new(),
+            watch_hit: Cell::new(None),
+            ram_cheats: Vec::new(),
         }
     }
 
+    pub fn set_cgb_mode(&mut self, cgb_mode: bool) {
+        self.cgb_mode = cgb_mode;
+        self.ppu.cgb_mode = cgb_mode;
+        self.ioram.borrow_mut().serial.cgb_mode = cgb_mode;
+    }
+
+    // called by STOP, only does something after KEY1 armed the switch
+    pub fn switch_speed(&mut self) {
+        if !self.cgb_mode || !self.speed_switch_armed {
+            return;
+        }
+        self.speed_switch_armed = false;
+        self.double_speed = !self.double_speed;
+        self.timer.double_speed = self.double_speed;
+        // STOP resets DIV
+        self.write8(0xFF04, 0);
+    }
+
     pub fn set_request_interrupt_fn(&mut self) {
         // let ptr: fn(InterruptType) = request_interrupt;
         let ioram = Rc::clone(&self.ioram);
@@ -42,8 +108,25 @@ impl Bus {
     //     self.ioram.interrupt_flags |= t;
     // }
 
+    pub fn set_boot_rom(&mut self, boot_rom: Vec<u8>) {
+        self.boot_rom = boot_rom;
+        self.boot_rom_mapped = true;
+    }
+
     pub fn read8(&self, address: usize) -> u8 {
+        let value = self.read8_unwatched(address);
+        if !self.watchpoints.is_empty() {
+            self.watch(address, value, false);
+        }
+        value
+    }
+    // for debuggers and tracers, doesn't trip any watchpoints
+    pub fn read8_unwatched(&self, address: usize) -> u8 {
         match address {
+            //boot ROM, the CGB one leaves a hole for the cartridge header
+            0..0x100 | 0x200..0x900 if self.boot_rom_mapped && address < self.boot_rom.len() => {
+                self.boot_rom[address]
+            }
             //Char/BG
             0x8000..0xA000 => self.ppu.vram_read(address),
             //cartridge ROM or Ext ram
@@ -71,8 +154,26 @@ impl Bus {
             0xFEA0..0xFF00 => 0,
 
             //IO section. LCD and TIMER are separated from it
+            0xFF44 => self.ly_override.unwrap_or(self.ppu.lcd.ly),
             0xFF40..=0xFF4B => self.ppu.lcd.read(address),
             0xFF04..=0xFF07 => self.timer.read_byte(address),
+            0xFF10..=0xFF3F => self.apu.read(address),
+            //KEY1
+            0xFF4D if self.cgb_mode => {
+                0x7E | ((self.double_speed as u8) << 7) | self.speed_switch_armed as u8
+            }
+            0xFF4D => 0xFF,
+            0xFF4F => self.ppu.vbk_read(),
+            0xFF50 => 0xFF,
+            //VRAM DMA
+            0xFF51..=0xFF55 if self.cgb_mode => self.hdma.read(address),
+            0xFF51..=0xFF55 => 0xFF,
+            //CGB palettes
+            0xFF68..=0xFF6B if self.cgb_mode => self.ppu.lcd.read(address),
+            0xFF68..=0xFF6B => 0xFF,
+            //SVBK
+            0xFF70 if self.cgb_mode => 0xF8 | self.wram_bank as u8,
+            0xFF70 => 0xFF,
             0xFF00..0xFF80 => self.ioram.borrow().read(address),
 
             //high ram/zero page
@@ -88,6 +189,9 @@ impl Bus {
         return val;
     }
     pub fn write8(&mut self, address: usize, value: u8) {
+        if !self.watchpoints.is_empty() {
+            self.watch(address, value, true);
+        }
         match address {
             //order matters here since ppu wram addres range is inside the cart range
             0x8000..0xA000 => self.ppu.vram_write(address, value),
@@ -112,7 +216,48 @@ impl Bus {
             0xFEA0..0xFF00 => {}
             //lcd part of io
             0xFF40..=0xFF4B => self.ppu.lcd.write(address, value),
-            0xFF04..=0xFF07 => self.timer.write_byte(address, value),
+            0xFF04..=0xFF07 => {
+                self.timer.write_byte(address, value);
+                // resetting DIV can clock the frame sequencer early
+                if self.timer.take_apu_event() {
+                    self.apu.frame_sequencer_tick();
+                }
+            }
+            //sound
+            0xFF10..=0xFF3F => self.apu.write(address, value),
+            0xFF4D => {
+                if self.cgb_mode {
+                    self.speed_switch_armed = value & 1 > 0;
+                }
+            }
+            0xFF4F => self.ppu.vbk_write(value),
+            0xFF50 => {
+                if value != 0 {
+                    self.boot_rom_mapped = false;
+                }
+            }
+            0xFF51..=0xFF55 => {
+                if self.cgb_mode {
+                    self.hdma.write(address, value);
+                    // general purpose DMA copies everything right away
+                    if self.hdma.active && !self.hdma.hblank_mode {
+                        while self.hdma.active {
+                            self.hdma_copy_block();
+                        }
+                    }
+                }
+            }
+            0xFF68..=0xFF6B => {
+                if self.cgb_mode {
+                    self.ppu.lcd.write(address, value)
+                }
+            }
+            0xFF70 => {
+                if self.cgb_mode {
+                    // bank 0 can't be mapped to D000, it selects bank 1 instead
+                    self.wram_bank = ((value & 0x07) as usize).max(1);
+                }
+            }
             //IO data
             0xFF00..0xFF80 => self.ioram.borrow_mut().write(address, value),
             //high ram/zero page
@@ -129,11 +274,63 @@ impl Bus {
         }
     }
 
+    // Reads for memory viewers. ROM at 4000-7FFF and cartridge RAM come from
+    // the given banks whatever the MBC has mapped, echo RAM mirrors C000-DDFF
+    // and OAM reads right through DMA.
+    pub fn peek(&self, address: u16, rom_bank: usize, sram_bank: usize) -> u8 {
+        match self.locate(address, rom_bank, sram_bank) {
+            Some((domain, bank, offset)) => self.domain_read(domain, bank, offset).unwrap_or(0xFF),
+            None if address == 0xFFFF => self.ie_register,
+            None => 0,
+        }
+    }
+
+    // Writes for memory editors that only change the byte itself: nothing
+    // gets switched, started or reset, see Bus::domain_write.
+    pub fn poke(
+        &mut self,
+        address: u16,
+        value: u8,
+        rom_bank: usize,
+        sram_bank: usize,
+    ) -> Result<(), String> {
+        match self.locate(address, rom_bank, sram_bank) {
+            Some((domain, bank, offset)) => self.domain_write(domain, bank, offset, value),
+            None if address == 0xFFFF => {
+                self.ie_register = value;
+                Ok(())
+            }
+            None => Err(format!("{:04X} can't be edited", address)),
+        }
+    }
+
+    fn watch(&self, address: usize, value: u8, write: bool) {
+        if self.watch_hit.get().is_some() {
+            return;
+        }
+        let watched = self.watchpoints.iter().any(|watchpoint| {
+            watchpoint.address as usize == address && watchpoint.access.matches(write)
+        });
+        if watched {
+            self.watch_hit.set(Some(WatchHit {
+                address: address as u16,
+                value,
+                write,
+            }));
+        }
+    }
+
+    fn wram_index(&self, address: usize) -> usize {
+        match address {
+            0xC000..0xD000 => address - 0xC000,
+            _ => self.wram_bank * 0x1000 + address - 0xD000,
+        }
+    }
     fn wram_write(&mut self, address: usize, value: u8) {
-        self.wram[(address - 0xC000) as usize] = value;
+        self.wram[self.wram_index(address)] = value;
     }
     fn wram_read(&self, address: usize) -> u8 {
-        return self.wram[(address - 0xC000) as usize];
+        return self.wram[self.wram_index(address)];
     }
 
     fn hram_write(&mut self, address: usize, value: u8) {
@@ -179,6 +376,32 @@ impl Bus {
         self.ppu.oam_write(address, value)
     }
 
+    // GameShark codes write their values every time VBlank starts
+    pub fn apply_ram_cheats(&mut self) {
+        for write in std::mem::take(&mut self.ram_cheats) {
+            write.apply(self);
+            self.ram_cheats.push(write);
+        }
+    }
+
+    // HBlank DMA moves one block every time the PPU enters HBlank
+    pub fn hdma_hblank(&mut self) {
+        if self.hdma.active && self.hdma.hblank_mode {
+            self.hdma_copy_block();
+        }
+    }
+
+    fn hdma_copy_block(&mut self) {
+        if let Some((src, dest)) = self.hdma.next_block() {
+            for i in 0..0x10 {
+                let val = self.read8(src + i);
+                self.ppu.vram_write(dest + i, val);
+            }
+            // 8 M-cycles per block in normal speed, twice as many in double speed
+            self.hdma_stall += if self.double_speed { 16 } else { 8 };
+        }
+    }
+
     pub fn dma_tick(&mut self) {
         if let Some((src, dest)) = self.ppu.lcd.dma.tick() {
             let val = self.read8(src);
@@ -186,3 +409,42 @@ impl Bus {
         }
     }
 }
+
+impl SaveState for Bus {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bytes(&self.wram);
+        writer.write_usize(self.wram_bank);
+        writer.write_bytes(&self.hram);
+        writer.write_u8(self.ie_register);
+        writer.write_bool(self.double_speed);
+        writer.write_bool(self.speed_switch_armed);
+        self.hdma.save_state(writer);
+        writer.write_bool(self.boot_rom_mapped);
+        self.ioram.borrow().save_state(writer);
+        self.timer.save_state(writer);
+        self.apu.save_state(writer);
+        self.ppu.save_state(writer);
+        if let Some(cart) = &self.cart {
+            cart.save_state(writer);
+        }
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        reader.read_bytes(&mut self.wram)?;
+        self.wram_bank = reader.read_usize()?.clamp(1, 7);
+        reader.read_bytes(&mut self.hram)?;
+        self.ie_register = reader.read_u8()?;
+        self.double_speed = reader.read_bool()?;
+        self.speed_switch_armed = reader.read_bool()?;
+        self.hdma.load_state(reader)?;
+        self.boot_rom_mapped = reader.read_bool()? && !self.boot_rom.is_empty();
+        self.ioram.borrow_mut().load_state(reader)?;
+        self.timer.load_state(reader)?;
+        self.apu.load_state(reader)?;
+        self.ppu.load_state(reader)?;
+        if let Some(cart) = &mut self.cart {
+            cart.load_state(reader)?;
+        }
+        Ok(())
+    }
+}
@@ -29,9 +29,26 @@ pub struct CartridgeInfo {
     pub pocket_camera: bool,
     pub sensor: bool,
     pub rumble: bool,
+    pub header_checksum: u8,
+    pub global_checksum: u16,
+    pub cgb_flag: u8,
 }
 
 impl CartridgeInfo {
+    // saves/game.<extension> in the directory above the ROM's
+    pub fn save_path(&self, extension: &str) -> String {
+        let path = Path::new(&self.path);
+        let file_stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let directory = path
+            .parent()
+            .and_then(Path::parent)
+            .unwrap_or(Path::new(".."));
+        directory
+            .join(format!("saves/{}.{}", file_stem, extension))
+            .to_string_lossy()
+            .to_string()
+    }
+
     pub fn from_data(path: &str, data: &Vec<u8>) -> Result<Self, String> {
         match CartridgeInfo::check_header_checksum(data) {
             true => println!("Checksum ok"),
@@ -339,10 +356,21 @@ impl CartridgeInfo {
             pocket_camera: cart_type == 0xfc,
             sensor: cart_type == 0x22,
             rumble: [0x1c, 0x1d, 0x1e, 0x22].contains(&cart_type),
+            header_checksum: data[0x014D],
+            global_checksum: ((data[0x014E] as u16) << 8) | data[0x014F] as u16,
+            cgb_flag: data[0x0143],
         };
         Ok(info)
     }
 
+    // 0x80: works on both DMG and CGB, 0xC0: CGB only
+    pub fn cgb_support(&self) -> bool {
+        self.cgb_flag & 0x80 > 0
+    }
+    pub fn cgb_only(&self) -> bool {
+        self.cgb_flag == 0xC0
+    }
+
     fn check_header_checksum(card: &[u8]) -> bool {
         let mut checksum: u8 = 0;
         for n in &card[0x0134..0x014d] {
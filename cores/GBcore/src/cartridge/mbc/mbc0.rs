@@ -1,6 +1,7 @@
 use crate::cartridge::cart_info::CartridgeInfo;
+use crate::state::{SaveState, StateReader, StateWriter};
 
-use super::MBC;
+use super::{read_flat_rom, write_flat_rom, MBC};
 
 pub struct MBC0 {
     data: Vec<u8>,
@@ -19,4 +20,19 @@ impl MBC for MBC0 {
         println!("No writing for non-mbc cards");
     }
     fn save_ram(&self) {}
+    fn read_rom(&self, bank: usize, offset: usize) -> u8 {
+        read_flat_rom(&self.data, bank, offset)
+    }
+    fn write_rom(&mut self, bank: usize, offset: usize, value: u8) {
+        write_flat_rom(&mut self.data, bank, offset, value);
+    }
+}
+
+// no banking registers or RAM to keep
+impl SaveState for MBC0 {
+    fn save_state(&self, _: &mut StateWriter) {}
+
+    fn load_state(&mut self, _: &mut StateReader) -> Result<(), String> {
+        Ok(())
+    }
 }
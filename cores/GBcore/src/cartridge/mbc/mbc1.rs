@@ -1,9 +1,10 @@
-use super::MBC;
+use super::{read_bank, read_ram_bank, write_bank, write_ram_bank, MBC};
 use crate::cartridge::cart_info::CartridgeInfo;
+use crate::state::{SaveState, StateReader, StateWriter};
 use std::{
     env, fs,
     ops::{AddAssign, SubAssign},
-    path::{Path, PathBuf},
+    path::PathBuf,
 };
 static SAVE_SKIPS: u8 = 20;
 pub struct MBC1 {
@@ -56,16 +57,7 @@ impl MBC1 {
         return mbc;
     }
     fn get_save_path(info: &CartridgeInfo) -> String {
-        let file_path = Path::new(&info.path);
-        let file_stem = file_path.file_stem().unwrap().to_str().unwrap();
-        let save_path = Path::new(&info.path)
-            .parent()
-            .unwrap()
-            .parent()
-            .unwrap()
-            .join(format!("saves/{}.sav", file_stem));
-
-        return save_path.to_str().unwrap().to_string();
+        info.save_path("sav")
     }
 
     fn create_rom_banks(data: &[u8], info: &CartridgeInfo) -> Vec<Vec<u8>> {
@@ -211,6 +203,45 @@ impl MBC for MBC1 {
             eprintln!("Failed to save RAM: {}", e);
         }
     }
+
+    fn rom_bank(&self) -> usize {
+        self.current_rom_bank_index & self.rom_bank_mask
+    }
+    fn read_rom(&self, bank: usize, offset: usize) -> u8 {
+        read_bank(&self.rom_banks, bank, offset)
+    }
+    fn write_rom(&mut self, bank: usize, offset: usize, value: u8) {
+        write_bank(&mut self.rom_banks, bank, offset, value);
+    }
+    fn ram_bank(&self) -> usize {
+        self.current_ram_bank_index
+            .min((self.ram_bank_count as usize).max(1) - 1)
+    }
+    fn read_ram(&self, bank: usize, offset: usize) -> u8 {
+        read_ram_bank(&self.ram_banks, bank, offset)
+    }
+    fn write_ram(&mut self, bank: usize, offset: usize, value: u8) {
+        write_ram_bank(&mut self.ram_banks, bank, offset, value);
+    }
+}
+impl SaveState for MBC1 {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bool(self.ram_enabled);
+        writer.write_u8(self.banking_mode);
+        writer.write_usize(self.current_rom_bank_index);
+        writer.write_usize(self.current_ram_bank_index);
+        writer.write_usize(self.upper_bits);
+        writer.write_banks(&self.ram_banks);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.ram_enabled = reader.read_bool()?;
+        self.banking_mode = reader.read_u8()?;
+        self.current_rom_bank_index = reader.read_usize()?;
+        self.current_ram_bank_index = reader.read_usize()?;
+        self.upper_bits = reader.read_usize()?;
+        reader.read_banks(&mut self.ram_banks)
+    }
 }
 // use super::MBC;
 // use crate::cartridge::cart_info::CartridgeInfo;
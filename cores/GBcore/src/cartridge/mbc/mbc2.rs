@@ -1,6 +1,7 @@
 use crate::cartridge::cart_info::CartridgeInfo;
+use crate::state::{SaveState, StateReader, StateWriter};
 
-use super::MBC;
+use super::{read_flat_rom, write_flat_rom, MBC};
 
 pub struct MBC2 {
     data: Vec<u8>,
@@ -38,4 +39,20 @@ impl MBC for MBC2 {
         //     eprintln!("Failed to save RAM: {}", e);
         // }
     }
+    fn read_rom(&self, bank: usize, offset: usize) -> u8 {
+        read_flat_rom(&self.data, bank, offset)
+    }
+    fn write_rom(&mut self, bank: usize, offset: usize, value: u8) {
+        write_flat_rom(&mut self.data, bank, offset, value);
+    }
+}
+
+// MBC2 isn't emulated yet, read and write are still stubs. Once it is,
+// its ROM bank register and the built-in 512x4 bit RAM belong in the state, until then a state can't be restored.
+impl SaveState for MBC2 {
+    fn save_state(&self, _: &mut StateWriter) {}
+
+    fn load_state(&mut self, _: &mut StateReader) -> Result<(), String> {
+        Err("Save states aren't supported for MBC2 cartridges yet".to_string())
+    }
 }
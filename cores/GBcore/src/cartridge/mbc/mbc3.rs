@@ -1,7 +1,8 @@
 use std::fs;
 
-use super::MBC;
+use super::{read_bank, read_ram_bank, write_bank, write_ram_bank, MBC};
 use crate::cartridge::cart_info::CartridgeInfo;
+use crate::state::{SaveState, StateReader, StateWriter};
 
 pub struct MBC3 {
     data: Vec<u8>,
@@ -167,4 +168,48 @@ impl MBC for MBC3 {
         //     eprintln!("Failed to save RAM: {}", e);
         // }
     }
+
+    fn rom_bank(&self) -> usize {
+        self.current_rom_bank
+    }
+    fn read_rom(&self, bank: usize, offset: usize) -> u8 {
+        read_bank(&self.rom_banks, bank, offset)
+    }
+    fn write_rom(&mut self, bank: usize, offset: usize, value: u8) {
+        write_bank(&mut self.rom_banks, bank, offset, value);
+    }
+    fn ram_bank(&self) -> usize {
+        self.current_ram_bank
+            .min((self.ram_bank_count as usize).max(1) - 1)
+    }
+    fn read_ram(&self, bank: usize, offset: usize) -> u8 {
+        read_ram_bank(&self.ram_banks, bank, offset)
+    }
+    fn write_ram(&mut self, bank: usize, offset: usize, value: u8) {
+        write_ram_bank(&mut self.ram_banks, bank, offset, value);
+    }
+}
+
+impl SaveState for MBC3 {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bytes(&self.rtc_registers);
+        writer.write_usize(self.current_rom_bank);
+        writer.write_usize(self.current_ram_bank);
+        writer.write_bool(self.ram_enabled);
+        writer.write_bool(self.rtc_latched);
+        writer.write_bool(self.rtc_selected);
+        writer.write_bool(self.rtc_enabled);
+        writer.write_banks(&self.ram_banks);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        reader.read_bytes(&mut self.rtc_registers)?;
+        self.current_rom_bank = reader.read_usize()?;
+        self.current_ram_bank = reader.read_usize()?;
+        self.ram_enabled = reader.read_bool()?;
+        self.rtc_latched = reader.read_bool()?;
+        self.rtc_selected = reader.read_bool()?;
+        self.rtc_enabled = reader.read_bool()?;
+        reader.read_banks(&mut self.ram_banks)
+    }
 }
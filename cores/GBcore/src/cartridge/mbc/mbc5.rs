@@ -1,5 +1,6 @@
-use super::MBC;
+use super::{read_bank, read_ram_bank, write_bank, write_ram_bank, MBC};
 use crate::cartridge::cart_info::CartridgeInfo;
+use crate::state::{SaveState, StateReader, StateWriter};
 use std::path::Path;
 
 pub struct MBC5 {
@@ -162,4 +163,39 @@ impl MBC for MBC5 {
         //     eprintln!("Failed to save RAM: {}", e);
         // }
     }
+
+    fn rom_bank(&self) -> usize {
+        self.current_rom_bank % self.rom_bank_count
+    }
+    fn read_rom(&self, bank: usize, offset: usize) -> u8 {
+        read_bank(&self.rom_banks, bank, offset)
+    }
+    fn write_rom(&mut self, bank: usize, offset: usize, value: u8) {
+        write_bank(&mut self.rom_banks, bank, offset, value);
+    }
+    fn ram_bank(&self) -> usize {
+        self.current_ram_bank % (self.ram_bank_count as usize).max(1)
+    }
+    fn read_ram(&self, bank: usize, offset: usize) -> u8 {
+        read_ram_bank(&self.ram_banks, bank, offset)
+    }
+    fn write_ram(&mut self, bank: usize, offset: usize, value: u8) {
+        write_ram_bank(&mut self.ram_banks, bank, offset, value);
+    }
+}
+
+impl SaveState for MBC5 {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bool(self.ram_enabled);
+        writer.write_usize(self.current_rom_bank);
+        writer.write_usize(self.current_ram_bank);
+        writer.write_banks(&self.ram_banks);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.ram_enabled = reader.read_bool()?;
+        self.current_rom_bank = reader.read_usize()?;
+        self.current_ram_bank = reader.read_usize()?;
+        reader.read_banks(&mut self.ram_banks)
+    }
 }
@@ -1,6 +1,7 @@
 use crate::cartridge::cart_info::CartridgeInfo;
+use crate::state::{SaveState, StateReader, StateWriter};
 
-use super::MBC;
+use super::{read_flat_rom, write_flat_rom, MBC};
 
 pub struct MBC7 {
     data: Vec<u8>,
@@ -37,4 +38,20 @@ impl MBC for MBC7 {
         //     eprintln!("Failed to save RAM: {}", e);
         // }
     }
+    fn read_rom(&self, bank: usize, offset: usize) -> u8 {
+        read_flat_rom(&self.data, bank, offset)
+    }
+    fn write_rom(&mut self, bank: usize, offset: usize, value: u8) {
+        write_flat_rom(&mut self.data, bank, offset, value);
+    }
+}
+
+// MBC7 isn't emulated yet, read and write are still stubs. Once it is,
+// its EEPROM belong in the state, until then a state can't be restored.
+impl SaveState for MBC7 {
+    fn save_state(&self, _: &mut StateWriter) {}
+
+    fn load_state(&mut self, _: &mut StateReader) -> Result<(), String> {
+        Err("Save states aren't supported for MBC7 cartridges yet".to_string())
+    }
 }
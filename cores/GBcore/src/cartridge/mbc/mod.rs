@@ -1,6 +1,7 @@
 use std::path::Path;
 
 use super::cart_info::CartridgeInfo;
+use crate::state::{SaveState, StateReader, StateWriter};
 
 pub mod mbc0;
 pub mod mbc1;
@@ -14,6 +15,63 @@ pub trait MBC {
     fn read(&self, address: usize) -> u8;
     fn write(&mut self, address: usize, value: u8);
     fn save_ram(&self);
+    // any ROM bank, mapped or not
+    fn read_rom(&self, bank: usize, offset: usize) -> u8;
+    // the bank mapped at 4000-7FFF
+    fn rom_bank(&self) -> usize {
+        1
+    }
+    // patches ROM in memory, the file stays as it is
+    fn write_rom(&mut self, bank: usize, offset: usize, value: u8);
+    // the RAM bank mapped at A000-BFFF
+    fn ram_bank(&self) -> usize {
+        0
+    }
+    // any RAM bank, whether it's mapped or RAM is enabled or not
+    fn read_ram(&self, _: usize, _: usize) -> u8 {
+        0xFF
+    }
+    fn write_ram(&mut self, _: usize, _: usize, _: u8) {}
+}
+
+// A byte of the banked ROM or RAM, 0xFF for banks or offsets the cartridge
+// doesn't have. Writes there go nowhere.
+pub fn read_bank(banks: &[Vec<u8>], bank: usize, offset: usize) -> u8 {
+    banks
+        .get(bank)
+        .and_then(|bank| bank.get(offset))
+        .copied()
+        .unwrap_or(0xFF)
+}
+
+pub fn write_bank(banks: &mut [Vec<u8>], bank: usize, offset: usize, value: u8) {
+    if let Some(byte) = banks.get_mut(bank).and_then(|bank| bank.get_mut(offset)) {
+        *byte = value;
+    }
+}
+
+// the same for a ROM kept in one piece, banks of 0x4000 bytes
+pub fn read_flat_rom(data: &[u8], bank: usize, offset: usize) -> u8 {
+    data.get(bank * 0x4000 + offset).copied().unwrap_or(0xFF)
+}
+
+pub fn write_flat_rom(data: &mut [u8], bank: usize, offset: usize, value: u8) {
+    if let Some(byte) = data.get_mut(bank * 0x4000 + offset) {
+        *byte = value;
+    }
+}
+
+// the same for cartridges that may have no RAM at all
+pub fn read_ram_bank(banks: &Option<Vec<Vec<u8>>>, bank: usize, offset: usize) -> u8 {
+    banks
+        .as_deref()
+        .map_or(0xFF, |banks| read_bank(banks, bank, offset))
+}
+
+pub fn write_ram_bank(banks: &mut Option<Vec<Vec<u8>>>, bank: usize, offset: usize, value: u8) {
+    if let Some(banks) = banks {
+        write_bank(banks, bank, offset, value);
+    }
 }
 
 pub enum MBCEnum {
@@ -62,10 +120,125 @@ impl MBC for MBCEnum {
             MBCEnum::MBC7(mbc) => mbc.save_ram(),
         }
     }
+    fn read_rom(&self, bank: usize, offset: usize) -> u8 {
+        match self {
+            MBCEnum::MBC0(mbc) => mbc.read_rom(bank, offset),
+            MBCEnum::MBC1(mbc) => mbc.read_rom(bank, offset),
+            MBCEnum::MBC2(mbc) => mbc.read_rom(bank, offset),
+            MBCEnum::MBC3(mbc) => mbc.read_rom(bank, offset),
+            MBCEnum::MBC5(mbc) => mbc.read_rom(bank, offset),
+            MBCEnum::MBC6(mbc) => mbc.read_rom(bank, offset),
+            MBCEnum::MBC7(mbc) => mbc.read_rom(bank, offset),
+        }
+    }
+    fn rom_bank(&self) -> usize {
+        match self {
+            MBCEnum::MBC0(mbc) => mbc.rom_bank(),
+            MBCEnum::MBC1(mbc) => mbc.rom_bank(),
+            MBCEnum::MBC2(mbc) => mbc.rom_bank(),
+            MBCEnum::MBC3(mbc) => mbc.rom_bank(),
+            MBCEnum::MBC5(mbc) => mbc.rom_bank(),
+            MBCEnum::MBC6(mbc) => mbc.rom_bank(),
+            MBCEnum::MBC7(mbc) => mbc.rom_bank(),
+        }
+    }
+    fn write_rom(&mut self, bank: usize, offset: usize, value: u8) {
+        match self {
+            MBCEnum::MBC0(mbc) => mbc.write_rom(bank, offset, value),
+            MBCEnum::MBC1(mbc) => mbc.write_rom(bank, offset, value),
+            MBCEnum::MBC2(mbc) => mbc.write_rom(bank, offset, value),
+            MBCEnum::MBC3(mbc) => mbc.write_rom(bank, offset, value),
+            MBCEnum::MBC5(mbc) => mbc.write_rom(bank, offset, value),
+            MBCEnum::MBC6(mbc) => mbc.write_rom(bank, offset, value),
+            MBCEnum::MBC7(mbc) => mbc.write_rom(bank, offset, value),
+        }
+    }
+    fn ram_bank(&self) -> usize {
+        match self {
+            MBCEnum::MBC0(mbc) => mbc.ram_bank(),
+            MBCEnum::MBC1(mbc) => mbc.ram_bank(),
+            MBCEnum::MBC2(mbc) => mbc.ram_bank(),
+            MBCEnum::MBC3(mbc) => mbc.ram_bank(),
+            MBCEnum::MBC5(mbc) => mbc.ram_bank(),
+            MBCEnum::MBC6(mbc) => mbc.ram_bank(),
+            MBCEnum::MBC7(mbc) => mbc.ram_bank(),
+        }
+    }
+    fn read_ram(&self, bank: usize, offset: usize) -> u8 {
+        match self {
+            MBCEnum::MBC0(mbc) => mbc.read_ram(bank, offset),
+            MBCEnum::MBC1(mbc) => mbc.read_ram(bank, offset),
+            MBCEnum::MBC2(mbc) => mbc.read_ram(bank, offset),
+            MBCEnum::MBC3(mbc) => mbc.read_ram(bank, offset),
+            MBCEnum::MBC5(mbc) => mbc.read_ram(bank, offset),
+            MBCEnum::MBC6(mbc) => mbc.read_ram(bank, offset),
+            MBCEnum::MBC7(mbc) => mbc.read_ram(bank, offset),
+        }
+    }
+    fn write_ram(&mut self, bank: usize, offset: usize, value: u8) {
+        match self {
+            MBCEnum::MBC0(mbc) => mbc.write_ram(bank, offset, value),
+            MBCEnum::MBC1(mbc) => mbc.write_ram(bank, offset, value),
+            MBCEnum::MBC2(mbc) => mbc.write_ram(bank, offset, value),
+            MBCEnum::MBC3(mbc) => mbc.write_ram(bank, offset, value),
+            MBCEnum::MBC5(mbc) => mbc.write_ram(bank, offset, value),
+            MBCEnum::MBC6(mbc) => mbc.write_ram(bank, offset, value),
+            MBCEnum::MBC7(mbc) => mbc.write_ram(bank, offset, value),
+        }
+    }
 
     // Implement other methods similarly
 }
 
+impl MBCEnum {
+    fn index(&self) -> u8 {
+        match self {
+            MBCEnum::MBC0(_) => 0,
+            MBCEnum::MBC1(_) => 1,
+            MBCEnum::MBC2(_) => 2,
+            MBCEnum::MBC3(_) => 3,
+            MBCEnum::MBC5(_) => 5,
+            MBCEnum::MBC6(_) => 6,
+            MBCEnum::MBC7(_) => 7,
+        }
+    }
+}
+
+impl SaveState for MBCEnum {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.index());
+        match self {
+            MBCEnum::MBC0(mbc) => mbc.save_state(writer),
+            MBCEnum::MBC1(mbc) => mbc.save_state(writer),
+            MBCEnum::MBC2(mbc) => mbc.save_state(writer),
+            MBCEnum::MBC3(mbc) => mbc.save_state(writer),
+            MBCEnum::MBC5(mbc) => mbc.save_state(writer),
+            MBCEnum::MBC6(mbc) => mbc.save_state(writer),
+            MBCEnum::MBC7(mbc) => mbc.save_state(writer),
+        }
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        let index = reader.read_u8()?;
+        if index != self.index() {
+            return Err(format!(
+                "Save state is for MBC{}, cartridge uses MBC{}",
+                index,
+                self.index()
+            ));
+        }
+        match self {
+            MBCEnum::MBC0(mbc) => mbc.load_state(reader),
+            MBCEnum::MBC1(mbc) => mbc.load_state(reader),
+            MBCEnum::MBC2(mbc) => mbc.load_state(reader),
+            MBCEnum::MBC3(mbc) => mbc.load_state(reader),
+            MBCEnum::MBC5(mbc) => mbc.load_state(reader),
+            MBCEnum::MBC6(mbc) => mbc.load_state(reader),
+            MBCEnum::MBC7(mbc) => mbc.load_state(reader),
+        }
+    }
+}
+
 pub fn create_mbc(data: Vec<u8>, info: &CartridgeInfo) -> MBCEnum {
     println!("mbc{}", info.mbc_index);
     let mbc = match info.mbc_index {
@@ -2,13 +2,18 @@ use std::{
     any::{Any, TypeId},
     fs,
 };
-mod cart_info;
+pub mod cart_info;
 mod mbc;
 use cart_info::CartridgeInfo;
 use mbc::{create_mbc, MBCEnum, MBC};
+
+use crate::cheats::RomPatch;
+use crate::state::{SaveState, StateReader, StateWriter};
 pub struct Cartridge {
     pub info: CartridgeInfo,
     mbc: MBCEnum,
+    // enabled Game Genie codes
+    pub patches: Vec<RomPatch>,
     // pub data: Vec<u8>,
     // pub rom_banks: Vec<Vec<u8>>,
     // pub ram_banks: Vec<Vec<u8>>,
@@ -26,7 +31,11 @@ impl Cartridge {
                 Ok(info) => {
                     let mbc = create_mbc(unwrapped_data, &info);
 
-                    let cartridge = Cartridge { info, mbc };
+                    let cartridge = Cartridge {
+                        info,
+                        mbc,
+                        patches: Vec::new(),
+                    };
 
                     Ok(cartridge)
                 }
@@ -49,11 +58,48 @@ impl Cartridge {
     }
 
     pub fn read(&self, address: usize) -> u8 {
-        self.mbc.read(address)
+        let value = self.mbc.read(address);
+        if self.patches.is_empty() || address >= 0x8000 {
+            return value;
+        }
+        self.patches
+            .iter()
+            .find(|patch| {
+                patch.address as usize == address && patch.compare.is_none_or(|c| c == value)
+            })
+            .map_or(value, |patch| patch.value)
     }
     pub fn write(&mut self, address: usize, value: u8) {
         self.mbc.write(address, value)
     }
+    pub fn rom_bank(&self) -> usize {
+        self.mbc.rom_bank()
+    }
+    // `offset` into ROM bank `bank`, whatever the MBC has mapped
+    pub fn read_rom(&self, bank: usize, offset: usize) -> u8 {
+        self.mbc.read_rom(bank, offset)
+    }
+    pub fn write_rom(&mut self, bank: usize, offset: usize, value: u8) {
+        self.mbc.write_rom(bank, offset, value)
+    }
+    pub fn ram_bank(&self) -> usize {
+        self.mbc.ram_bank()
+    }
+    pub fn read_ram(&self, bank: usize, offset: usize) -> u8 {
+        self.mbc.read_ram(bank, offset)
+    }
+    pub fn write_ram(&mut self, bank: usize, offset: usize, value: u8) {
+        self.mbc.write_ram(bank, offset, value)
+    }
+}
+impl SaveState for Cartridge {
+    fn save_state(&self, writer: &mut StateWriter) {
+        self.mbc.save_state(writer);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.mbc.load_state(reader)
+    }
 }
 // fn get_mbc(cartridge_data: Vec<u8>, info: &CartridgeInfo) -> Box<MBC> {
 //     match info.mbc_index {
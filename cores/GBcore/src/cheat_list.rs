@@ -0,0 +1,169 @@
+use sdl2::keyboard::Keycode;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+use crate::ppu::Color;
+use crate::rendering::{DebugImage, LABEL_COLOR, TEXT_COLOR};
+use crate::GameBoy;
+
+static WIDTH: usize = 584;
+static HEIGHT: usize = 380;
+static TOP_Y: usize = 22;
+static ROWS: usize = 25;
+static CODE_X: usize = 48;
+static NAME_X: usize = CODE_X + 14 * 8;
+// ABC-DEF-GHI is the longest code there is
+static CODE_LENGTH: usize = 11;
+static OFF_COLOR: Color = Color::rgb(0x90, 0x90, 0x90);
+static SELECTED_COLOR: Color = Color::rgb(0x30, 0x50, 0xA0);
+
+enum Action {
+    Toggle(usize),
+    Remove(usize),
+    Add(String),
+}
+
+// The cheats of the running game, F9 opens and closes it. Codes are
+// typed right into the window, every change is saved to the cheat file.
+pub struct CheatList {
+    canvas: Canvas<Window>,
+    selected: usize,
+    // the code being typed in
+    typed: String,
+    // since the last frame, done on the next one
+    actions: Vec<Action>,
+    message: String,
+}
+
+impl CheatList {
+    pub fn open(sdl_context: &sdl2::Sdl) -> Result<Self, String> {
+        let window = sdl_context
+            .video()?
+            .window("Rustboy cheats", WIDTH as u32, HEIGHT as u32)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+        Ok(CheatList {
+            canvas,
+            selected: 0,
+            typed: String::new(),
+            actions: Vec::new(),
+            message: String::new(),
+        })
+    }
+
+    pub fn window_id(&self) -> u32 {
+        self.canvas.window().id()
+    }
+
+    pub fn key(&mut self, key: Keycode) {
+        self.message.clear();
+        match key {
+            Keycode::UP => self.selected = self.selected.saturating_sub(1),
+            Keycode::DOWN => self.selected += 1,
+            Keycode::SPACE => self.actions.push(Action::Toggle(self.selected)),
+            Keycode::DELETE => self.actions.push(Action::Remove(self.selected)),
+            Keycode::RETURN if self.typed.is_empty() => {
+                self.actions.push(Action::Toggle(self.selected))
+            }
+            Keycode::RETURN => self
+                .actions
+                .push(Action::Add(std::mem::take(&mut self.typed))),
+            Keycode::BACKSPACE => _ = self.typed.pop(),
+            Keycode::ESCAPE => self.typed.clear(),
+            Keycode::MINUS if self.typed.len() < CODE_LENGTH => self.typed.push('-'),
+            _ => {
+                let name = key.name();
+                let digit = match name.chars().next() {
+                    Some(c) if name.len() == 1 && c.is_ascii_hexdigit() => Some(c),
+                    _ => None,
+                };
+                if let Some(digit) = digit.filter(|_| self.typed.len() < CODE_LENGTH) {
+                    self.typed.push(digit.to_ascii_uppercase());
+                }
+            }
+        }
+    }
+
+    // Applies what was typed to the game's cheats, then redraws the list
+    pub fn update(&mut self, gameboy: &mut GameBoy) {
+        let changed = !self.actions.is_empty();
+        let cheats = &mut gameboy.cheats;
+        for action in std::mem::take(&mut self.actions) {
+            match action {
+                Action::Toggle(index) => {
+                    if let Some(cheat) = cheats.list.get_mut(index) {
+                        cheat.enabled = !cheat.enabled;
+                    }
+                }
+                Action::Remove(index) => {
+                    if index < cheats.len() {
+                        cheats.list.remove(index);
+                    }
+                }
+                Action::Add(code) => match cheats.add(&code, "") {
+                    Ok(()) => self.selected = cheats.len() - 1,
+                    Err(err) => {
+                        self.message = err;
+                        self.typed = code;
+                    }
+                },
+            }
+        }
+        if changed {
+            if let Err(err) = cheats.save() {
+                self.message = err;
+            }
+            gameboy.apply_cheats();
+        }
+        self.selected = self.selected.min(gameboy.cheats.len().saturating_sub(1));
+
+        let image = self.draw(gameboy);
+        if let Err(err) = image.present(&mut self.canvas) {
+            println!("Cheat list: {}", err);
+        }
+    }
+
+    fn draw(&self, gameboy: &GameBoy) -> DebugImage {
+        let mut image = DebugImage::new(WIDTH, HEIGHT);
+        let cheats = &gameboy.cheats;
+        image.text(8, 4, &format!("CHEATS {}", cheats.len()), LABEL_COLOR);
+        if cheats.is_empty() {
+            image.text(8, TOP_Y, "NONE YET", OFF_COLOR);
+        }
+        // the selected one stays on screen
+        let first = (self.selected + 1).saturating_sub(ROWS);
+        for (row, (index, cheat)) in cheats
+            .list
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(ROWS)
+            .enumerate()
+        {
+            let y = TOP_Y + row * 12;
+            if index == self.selected {
+                image.fill(6, y - 2, WIDTH - 12, 14, SELECTED_COLOR);
+            }
+            let (state, color) = if cheat.enabled {
+                ("ON", TEXT_COLOR)
+            } else {
+                ("OFF", OFF_COLOR)
+            };
+            image.text(8, y, state, color);
+            image.text(CODE_X, y, &cheat.code, color);
+            image.text(NAME_X, y, &cheat.name, color);
+        }
+
+        let y = HEIGHT - 44;
+        image.text(8, y, &format!("CODE: {}_", self.typed), TEXT_COLOR);
+        image.text(8, y + 14, &self.message, LABEL_COLOR);
+        image.text(
+            8,
+            HEIGHT - 16,
+            "SPACE ON/OFF  DEL REMOVE  TYPE A CODE AND ENTER TO ADD",
+            LABEL_COLOR,
+        );
+        image
+    }
+}
@@ -0,0 +1,284 @@
+use std::fs;
+use std::path::Path;
+
+use crate::bus::Bus;
+use crate::cartridge::cart_info::CartridgeInfo;
+use crate::memory::MemoryDomain;
+
+// Game Genie: reads of `address` return `value` instead, only while the
+// ROM there holds `compare` when there is one. That's how a code for
+// switchable ROM sticks to a single bank.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RomPatch {
+    pub address: u16,
+    pub value: u8,
+    pub compare: Option<u8>,
+}
+
+// GameShark: `value` is written to `address` at the start of every VBlank.
+// `bank` is 0x8X for cartridge RAM bank X, 0x9X for WRAM bank X at
+// D000-DFFF and anything else for whatever is mapped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RamWrite {
+    pub bank: u8,
+    pub address: u16,
+    pub value: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheatCode {
+    GameGenie(RomPatch),
+    GameShark(RamWrite),
+}
+
+fn hex_digits(text: &str) -> Option<Vec<u8>> {
+    text.chars()
+        .map(|c| c.to_digit(16).map(|digit| digit as u8))
+        .collect()
+}
+
+impl CheatCode {
+    // ABC-DEF-GHI or ABC-DEF for Game Genie, ABCDEFGH for GameShark
+    pub fn parse(text: &str) -> Result<CheatCode, String> {
+        let invalid = || format!("Not a Game Genie or GameShark code: {}", text);
+        let digits = hex_digits(&text.replace('-', "")).ok_or_else(invalid)?;
+        match digits.len() {
+            6 | 9 => {
+                // AB is the value, FCDE the address with F inverted, GI the
+                // compare byte scrambled, H is a checksum nobody checks
+                let address = ((digits[5] ^ 0xF) as u16) << 12
+                    | (digits[2] as u16) << 8
+                    | (digits[3] as u16) << 4
+                    | digits[4] as u16;
+                if address >= 0x8000 {
+                    return Err(format!("{} patches {:04X}, which isn't ROM", text, address));
+                }
+                let compare = (digits.len() == 9)
+                    .then(|| (digits[6] << 4 | digits[8]).rotate_right(2) ^ 0xBA);
+                Ok(CheatCode::GameGenie(RomPatch {
+                    address,
+                    value: digits[0] << 4 | digits[1],
+                    compare,
+                }))
+            }
+            8 => {
+                // bank, value and the address with its low byte first
+                let bytes: Vec<u8> = digits
+                    .chunks(2)
+                    .map(|pair| pair[0] << 4 | pair[1])
+                    .collect();
+                let address = u16::from_le_bytes([bytes[2], bytes[3]]);
+                if address < 0x8000 {
+                    return Err(format!("{} writes {:04X}, which is ROM", text, address));
+                }
+                Ok(CheatCode::GameShark(RamWrite {
+                    bank: bytes[0],
+                    address,
+                    value: bytes[1],
+                }))
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl RamWrite {
+    // straight into the memory, whatever the MBC has mapped or the PPU is doing
+    pub fn apply(&self, bus: &mut Bus) {
+        let sram_bank = bus.cart.as_ref().map_or(0, |cart| cart.ram_bank());
+        let rom_bank = bus.cart.as_ref().map_or(1, |cart| cart.rom_bank());
+        let Some((domain, bank, offset)) = bus.locate(self.address, rom_bank, sram_bank) else {
+            if self.address == 0xFFFF {
+                bus.ie_register = self.value;
+            }
+            return;
+        };
+        let bank = match (domain, self.bank >> 4) {
+            (MemoryDomain::Sram, 0x8) => (self.bank & 0x0F) as usize,
+            (MemoryDomain::Wram, 0x9) if bank > 0 => ((self.bank & 0x07) as usize).max(1),
+            _ => bank,
+        };
+        // a bank the cartridge doesn't have just doesn't get the write
+        _ = bus.domain_write(domain, bank, offset, self.value);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Cheat {
+    // the way it was typed in, upper case
+    pub code: String,
+    pub name: String,
+    pub enabled: bool,
+    pub parsed: CheatCode,
+}
+
+impl Cheat {
+    pub fn new(code: &str, name: &str) -> Result<Cheat, String> {
+        let code = code.trim().to_ascii_uppercase();
+        Ok(Cheat {
+            parsed: CheatCode::parse(&code)?,
+            code,
+            name: name.trim().to_string(),
+            enabled: true,
+        })
+    }
+}
+
+// The cheats of one game. They're kept in saves/game.cht next to the
+// battery saves, one `on|off code name` per line.
+#[derive(Debug, Default)]
+pub struct Cheats {
+    pub list: Vec<Cheat>,
+    // where save() writes them
+    pub path: Option<String>,
+}
+
+impl Cheats {
+    pub fn parse(text: &str) -> Result<Cheats, String> {
+        let mut cheats = Cheats::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (first, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            // a line with just a code is on
+            let (enabled, line) = match first {
+                "on" => (true, rest.trim()),
+                "off" => (false, rest.trim()),
+                _ => (true, line),
+            };
+            let (code, name) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let mut cheat =
+                Cheat::new(code, name).map_err(|err| format!("Line {}: {}", number + 1, err))?;
+            cheat.enabled = enabled;
+            cheats.list.push(cheat);
+        }
+        Ok(cheats)
+    }
+
+    // no file yet is no cheats yet
+    pub fn load(path: &str) -> Result<Cheats, String> {
+        let mut cheats = match fs::read_to_string(path) {
+            Ok(text) => Cheats::parse(&text).map_err(|err| format!("{}: {}", path, err))?,
+            Err(_) if !Path::new(path).exists() => Cheats::default(),
+            Err(err) => return Err(format!("Couldn't read {}: {}", path, err)),
+        };
+        cheats.path = Some(path.to_string());
+        Ok(cheats)
+    }
+
+    pub fn for_rom(info: &CartridgeInfo) -> Result<Cheats, String> {
+        Cheats::load(&info.save_path("cht"))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(directory) = Path::new(path).parent() {
+            fs::create_dir_all(directory).map_err(|err| err.to_string())?;
+        }
+        let text: String = self
+            .list
+            .iter()
+            .map(|cheat| {
+                let state = if cheat.enabled { "on" } else { "off" };
+                format!("{} {} {}", state, cheat.code, cheat.name)
+                    .trim_end()
+                    .to_string()
+                    + "\n"
+            })
+            .collect();
+        fs::write(path, text).map_err(|err| format!("Couldn't write {}: {}", path, err))
+    }
+
+    pub fn add(&mut self, code: &str, name: &str) -> Result<(), String> {
+        self.list.push(Cheat::new(code, name)?);
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    // Hands the enabled codes to the cartridge and the bus, after every change
+    pub fn apply(&self, bus: &mut Bus) {
+        let enabled = || self.list.iter().filter(|cheat| cheat.enabled);
+        bus.ram_cheats = enabled()
+            .filter_map(|cheat| match cheat.parsed {
+                CheatCode::GameShark(write) => Some(write),
+                _ => None,
+            })
+            .collect();
+        if let Some(cart) = &mut bus.cart {
+            cart.patches = enabled()
+                .filter_map(|cheat| match cheat.parsed {
+                    CheatCode::GameGenie(patch) => Some(patch),
+                    _ => None,
+                })
+                .collect();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn game_genie() {
+        // value 00, address B^F A 1 7, compare C9 rotated right by two, xor BA
+        assert_eq!(
+            CheatCode::parse("00A-17B-C49"),
+            Ok(CheatCode::GameGenie(RomPatch {
+                address: 0x4A17,
+                value: 0x00,
+                compare: Some(0xC8),
+            }))
+        );
+        assert_eq!(
+            CheatCode::parse("FAA-13F"),
+            Ok(CheatCode::GameGenie(RomPatch {
+                address: 0x0A13,
+                value: 0xFA,
+                compare: None,
+            }))
+        );
+    }
+
+    #[test]
+    fn gameshark() {
+        // Pokemon Red/Blue walk through walls: bank 01, value 01, CD38 low byte first
+        assert_eq!(
+            CheatCode::parse("010138CD"),
+            Ok(CheatCode::GameShark(RamWrite {
+                bank: 0x01,
+                address: 0xCD38,
+                value: 0x01,
+            }))
+        );
+        assert_eq!(
+            CheatCode::parse("019947d3"),
+            Ok(CheatCode::GameShark(RamWrite {
+                bank: 0x01,
+                address: 0xD347,
+                value: 0x99,
+            }))
+        );
+    }
+
+    #[test]
+    fn rejects_bad_codes() {
+        // F000-FFFF once the top digit is inverted
+        assert!(CheatCode::parse("00A-170").is_err());
+        assert!(CheatCode::parse("01001234").is_err());
+        assert!(CheatCode::parse("00A-17G").is_err());
+        assert!(CheatCode::parse("1234567").is_err());
+        assert!(CheatCode::parse("").is_err());
+    }
+}
@@ -166,6 +166,10 @@ impl CPU {
                     &self.current_instruction.register_1.clone(),
                     self.fetched_data,
                 );
+                // LD B,B is the software breakpoint
+                if self.detect_test_result && self.current_instruction.opcode == 0x40 {
+                    self.check_test_result();
+                }
             }
             InstructionType::INC => {
                 let new_val = self.fetched_data.wrapping_add(1);
@@ -266,6 +270,7 @@ impl CPU {
             InstructionType::STOP => {
                 // println!("Stop executed");
                 // exit(1);
+                self.bus.switch_speed();
             }
             InstructionType::RLA => {
                 let old_c = self.flag_c() as u8;
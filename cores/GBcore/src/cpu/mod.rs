@@ -5,7 +5,17 @@ pub mod fetch;
 #[path = "cpu_interrupts.rs"]
 pub mod interrupts;
 use super::instruction::*;
+use super::state::{SaveState, StateReader, StateWriter};
+use super::trace::Tracer;
 use super::Bus;
+
+// What a Mooneye test ROM reports through its LD B,B breakpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestResult {
+    Passed,
+    Failed,
+}
+
 pub struct CPU {
     pub regs: Registers,
     pub fetched_data: u16,
@@ -16,6 +26,12 @@ pub struct CPU {
     pub int_master_enabled: bool,
     pub ime_enabling: bool,
     pub bus: Bus,
+    // T-cycles at the normal 4MHz clock since power on, double speed counts half
+    pub cycles: u64,
+    // off by default, games are free to use LD B,B as a plain NOP
+    pub detect_test_result: bool,
+    pub test_result: Option<TestResult>,
+    pub tracer: Option<Tracer>,
     af_count: u32,
 }
 
@@ -34,6 +50,21 @@ impl Registers {
             pc: 0x100,
         }
     }
+    // power on state, the boot ROM sets everything up from here
+    pub fn zeroed() -> Self {
+        Registers {
+            a: 0,
+            f: 0,
+            b: 0,
+            c: 0,
+            d: 0,
+            e: 0,
+            h: 0,
+            l: 0,
+            sp: 0,
+            pc: 0,
+        }
+    }
     pub fn get_flags_mnemonic(&self) -> String {
         let z = if self.f & 0b10000000 > 0 { "Z" } else { "-" };
         let n = if self.f & 0b01000000 > 0 { "N" } else { "-" };
@@ -55,6 +86,10 @@ impl CPU {
             ime_enabling: false,
             af_count: 0,
             bus: bus,
+            cycles: 0,
+            detect_test_result: false,
+            test_result: None,
+            tracer: None,
             current_instruction: Instruction {
                 ..Default::default()
             },
@@ -65,51 +100,17 @@ impl CPU {
         // println!("cpu step");
         let mut res = 0i8;
         if !self.halted {
+            if let Some(mut tracer) = self.tracer.take() {
+                tracer.trace(self);
+                self.tracer = Some(tracer);
+            }
             let opcode: u8 = self.bus.read8(self.regs.pc as usize);
-            // let pc = self.regs.pc.clone();
             self.current_instruction = Instruction::from_opcode(&opcode);
             self.increment_pointer(1);
             self.destination_is_mem = false;
 
-            // let following_byte = self.bus.read8((self.regs.pc) as usize);
-            // let third_byte = self.bus.read8((self.regs.pc + 1) as usize);
-            // res = format!("A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}\n",
-            //     self.regs.a,
-            //     self.regs.f,
-            //     self.regs.b,
-            //     self.regs.c,
-            //     self.regs.d,
-            //     self.regs.e,
-            //     self.regs.h,
-            //     self.regs.l,
-            //     self.regs.sp,
-            //     pc,
-            //     self.bus.read(pc as usize),
-            //     self.bus.read(pc as usize + 1),
-            //     self.bus.read(pc as usize + 2),
-            //     self.bus.read(pc as usize + 3),
-            // );
-            // println!(
-            //     "{:04X} {} ({:02X} {:02X} {:02X}) A:{:02X} F:{} BC:{:02X}{:02X} DE:{:02X}{:02X} HL:{:02X}{:02X} SP: {:04X}",
-            //     pc,
-            //     self.current_instruction.to_string(),
-            //     opcode,
-            //     following_byte,
-            //     third_byte,
-            //     self.regs.a,
-            //     self.regs.get_flags_mnemonic(),
-            //     self.regs.b,
-            //     self.regs.c,
-            //     self.regs.d,
-            //     self.regs.e,
-            //     self.regs.h,
-            //     self.regs.l,
-            //     self.regs.sp
-            // );
-
             self.emu_cycles(1);
             self.fetch_data();
-            // self.emu_dbg.update();
 
             res = self.execute();
         } else {
@@ -124,6 +125,14 @@ impl CPU {
                 self.af_count += 1;
             }
         }
+        // the CPU is stopped while VRAM DMA copies
+        loop {
+            let stall = std::mem::take(&mut self.bus.hdma_stall);
+            if stall == 0 {
+                break;
+            }
+            self.emu_cycles(stall);
+        }
         if self.int_master_enabled {
             let flags = self.bus.ioram.borrow().interrupt_flags;
             self.bus.ioram.borrow_mut().interrupt_flags = self.handle_interrupts(flags);
@@ -134,16 +143,42 @@ impl CPU {
         }
         return res;
     }
+    // Mooneye test ROMs run LD B,B when they are done, with the Fibonacci
+    // numbers in B-L on success and 0x42 in all of them on failure
+    pub(crate) fn check_test_result(&mut self) {
+        let regs = &self.regs;
+        match [regs.b, regs.c, regs.d, regs.e, regs.h, regs.l] {
+            [3, 5, 8, 13, 21, 34] => self.test_result = Some(TestResult::Passed),
+            [0x42, 0x42, 0x42, 0x42, 0x42, 0x42] => self.test_result = Some(TestResult::Failed),
+            _ => {}
+        }
+    }
     pub fn emu_cycles(&mut self, cycles: u32) {
         for _ in 0..cycles {
-            for _ in 0..4 {
+            for n in 0..4 {
                 // ctx.ticks++;
                 // println!("call tick from CPU");
 
                 self.bus.timer.tick();
-                self.bus.ppu.tick();
+                if self.bus.timer.take_apu_event() {
+                    self.bus.apu.frame_sequencer_tick();
+                }
+                // in double speed mode the PPU and APU keep their pace and only see every other tick
+                if !self.bus.double_speed || n & 1 == 0 {
+                    self.bus.apu.tick();
+                    self.bus.ppu.tick();
+                    if self.bus.ppu.take_hblank_event() {
+                        self.bus.hdma_hblank();
+                    }
+                    if self.bus.ppu.take_vblank_event() {
+                        self.bus.apply_ram_cheats();
+                    }
+                }
             }
+            let elapsed = if self.bus.double_speed { 2 } else { 4 };
+            self.bus.ioram.borrow_mut().serial_tick(4, elapsed);
             self.bus.dma_tick();
+            self.cycles += elapsed as u64;
         }
     }
 
@@ -175,3 +210,47 @@ pub struct Registers {
     pub pc: u16,
     pub sp: u16,
 }
+
+impl SaveState for CPU {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.regs.a);
+        writer.write_u8(self.regs.f);
+        writer.write_u8(self.regs.b);
+        writer.write_u8(self.regs.c);
+        writer.write_u8(self.regs.d);
+        writer.write_u8(self.regs.e);
+        writer.write_u8(self.regs.h);
+        writer.write_u8(self.regs.l);
+        writer.write_u16(self.regs.sp);
+        writer.write_u16(self.regs.pc);
+        writer.write_u16(self.fetched_data);
+        writer.write_usize(self.mem_dest);
+        writer.write_bool(self.destination_is_mem);
+        writer.write_bool(self.halted);
+        writer.write_bool(self.int_master_enabled);
+        writer.write_bool(self.ime_enabling);
+        writer.write_u64(self.cycles);
+        self.bus.save_state(writer);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.regs.a = reader.read_u8()?;
+        self.regs.f = reader.read_u8()?;
+        self.regs.b = reader.read_u8()?;
+        self.regs.c = reader.read_u8()?;
+        self.regs.d = reader.read_u8()?;
+        self.regs.e = reader.read_u8()?;
+        self.regs.h = reader.read_u8()?;
+        self.regs.l = reader.read_u8()?;
+        self.regs.sp = reader.read_u16()?;
+        self.regs.pc = reader.read_u16()?;
+        self.fetched_data = reader.read_u16()?;
+        self.mem_dest = reader.read_usize()?;
+        self.destination_is_mem = reader.read_bool()?;
+        self.halted = reader.read_bool()?;
+        self.int_master_enabled = reader.read_bool()?;
+        self.ime_enabling = reader.read_bool()?;
+        self.cycles = reader.read_u64()?;
+        self.bus.load_state(reader)
+    }
+}
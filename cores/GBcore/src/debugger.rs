@@ -0,0 +1,737 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+
+use crate::disasm::{self, mapped_bank};
+use crate::instruction::{Instruction, InstructionType};
+use crate::memory::MemoryDomain;
+use crate::GameBoy;
+
+// executed instructions kept around to disassemble what led up to PC
+static HISTORY_LENGTH: usize = 4;
+// instructions shown from PC on
+static DISASSEMBLY_AHEAD: usize = 6;
+// code that moves SP around by hand never returns, don't let that pile up
+static CALL_STACK_LIMIT: usize = 256;
+// DI, the one instruction that clears IME without an interrupt
+static DI: u8 = 0xF3;
+
+static HELP: &str = "Commands:
+  step|s [n]               run n instructions (1)
+  next|n                   step over calls and RSTs
+  finish|f                 run until the current function returns
+  continue|c [frames]      run until a breakpoint or watchpoint hits
+  break|b [bank:]<addr>    break on PC, the bank only counts in 4000-7FFF
+  delete [n]               remove breakpoint n, or all of them
+  watch|w <addr> [r|w|rw]  break when the CPU reads and/or writes addr (rw)
+  unwatch [n]              remove watchpoint n, or all of them
+  info|i                   list breakpoints and watchpoints
+  regs|r                   show the registers
+  set <reg> <value>        a f b c d e h l af bc de hl sp pc
+  mem|x <addr> [n]         dump n bytes (64)
+  write <addr> <byte>...   write through the bus like the CPU would
+  peek <domain> [bank:]<offset> [n]
+                           dump n bytes (64) of rom sram wram vram oam hram io
+  poke <domain> [bank:]<offset> <byte>...
+                           change memory without side effects or bank switches
+  disasm|d [addr] [n]      disassemble n instructions, around PC by default
+  backtrace|bt             calls and interrupts that led to PC
+  quit|q
+An empty line repeats the last command. Numbers are hex except the counts.
+Addresses can be labels from the symbol file too.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl Access {
+    pub fn matches(self, write: bool) -> bool {
+        match self {
+            Access::Read => !write,
+            Access::Write => write,
+            Access::ReadWrite => true,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Access> {
+        match name {
+            "r" => Some(Access::Read),
+            "w" => Some(Access::Write),
+            "rw" => Some(Access::ReadWrite),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub address: u16,
+    pub access: Access,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchHit {
+    pub address: u16,
+    pub value: u8,
+    pub write: bool,
+}
+
+// The bank only narrows down addresses in ROM, 0000-3FFF is always bank 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub address: u16,
+    pub bank: Option<usize>,
+}
+
+impl Breakpoint {
+    fn hit(&self, pc: u16, mapped_bank: Option<usize>) -> bool {
+        match (self.bank, mapped_bank) {
+            (Some(bank), Some(mapped_bank)) => self.address == pc && bank == mapped_bank,
+            _ => self.address == pc,
+        }
+    }
+}
+
+// A call or interrupt that hasn't returned yet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Frame {
+    // where the return address sits, the frame is gone once SP moves above it
+    sp: u16,
+    // the CALL or RST, or the instruction an interrupt returns to
+    site: u16,
+    bank: Option<usize>,
+    interrupt: bool,
+}
+
+// Why the debugger got control back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    Breakpoint(usize),
+    Watchpoint(WatchHit),
+    // a step, next or finish got where it was going
+    Done,
+    // ran the frames it was asked to
+    Frames,
+}
+
+// What the frontend does once the prompt closes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resume {
+    Continue,
+    Quit,
+}
+
+// Command line debugger on stdin/stdout. Headless, `continue` runs the
+// machine right from the prompt. Under a frontend it hands the machine back
+// and the frontend keeps running frames through run_frame, which stops at
+// breakpoints and watchpoints.
+pub struct Debugger {
+    pub breakpoints: Vec<Breakpoint>,
+    headless: bool,
+    // addresses of the last instructions run under the debugger
+    history: VecDeque<u16>,
+    // only known for code that ran under the debugger
+    calls: Vec<Frame>,
+    last_command: String,
+}
+
+// 01:4000 Main+$1A
+fn describe(gameboy: &GameBoy, address: u16, bank: Option<usize>) -> String {
+    let location = match bank {
+        Some(bank) => format!("{:02X}:{:04X}", bank, address),
+        None => format!("   {:04X}", address),
+    };
+    match gameboy.symbols.describe(address, bank) {
+        Some(label) => format!("{} {}", location, label),
+        None => location,
+    }
+}
+
+fn location(gameboy: &GameBoy, address: u16) -> String {
+    describe(gameboy, address, mapped_bank(&gameboy.cpu.bus, address))
+}
+
+fn parse_address(text: &str) -> Result<u16, String> {
+    let digits = text.trim_start_matches('$').trim_start_matches("0x");
+    u16::from_str_radix(digits, 16).map_err(|_| format!("Not an address: {}", text))
+}
+
+// [bank:]address or a label, the bank is None when it doesn't matter
+fn parse_target(gameboy: &GameBoy, text: &str) -> Result<(Option<usize>, u16), String> {
+    if let Some((bank, address)) = gameboy.symbols.find(text) {
+        let bank = (0x4000..0x8000).contains(&address).then_some(bank);
+        return Ok((bank, address));
+    }
+    match text.split_once(':') {
+        Some((bank, address)) => {
+            let bank =
+                usize::from_str_radix(bank, 16).map_err(|_| format!("Not a bank: {}", bank))?;
+            Ok((Some(bank), parse_address(address)?))
+        }
+        None => parse_address(text)
+            .map(|address| (None, address))
+            .map_err(|_| format!("Not an address or label: {}", text)),
+    }
+}
+
+fn parse_byte(text: &str) -> Result<u8, String> {
+    let digits = text.trim_start_matches('$').trim_start_matches("0x");
+    u8::from_str_radix(digits, 16).map_err(|_| format!("Not a byte: {}", text))
+}
+
+// [bank:]offset into a memory domain
+fn parse_domain_target(text: &str) -> Result<(usize, usize), String> {
+    let (bank, offset) = text.split_once(':').unwrap_or(("0", text));
+    let bank = usize::from_str_radix(bank, 16).map_err(|_| format!("Not a bank: {}", bank))?;
+    Ok((bank, parse_address(offset)? as usize))
+}
+
+fn parse_domain(text: Option<&&str>) -> Result<MemoryDomain, String> {
+    let text = text.ok_or("Which memory? rom sram wram vram oam hram io")?;
+    MemoryDomain::from_name(text).ok_or_else(|| format!("No such memory: {}", text))
+}
+
+fn parse_count(text: Option<&&str>, default: u32) -> Result<u32, String> {
+    match text {
+        Some(text) => text
+            .parse::<u32>()
+            .ok()
+            .filter(|count| *count > 0)
+            .ok_or_else(|| format!("Not a count: {}", text)),
+        None => Ok(default),
+    }
+}
+
+// 16 bytes a row, `start` is the address or offset of the first one
+fn dump(start: usize, bytes: &[u8]) {
+    for (row, chunk) in bytes.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02X}", byte)).collect();
+        let text: String = chunk
+            .iter()
+            .map(|byte| match byte {
+                0x20..0x7F => *byte as char,
+                _ => '.',
+            })
+            .collect();
+        println!("{:04X}  {: <47}  {}", start + row * 16, hex.join(" "), text);
+    }
+}
+
+// one instruction at `address`, returns the line and where the next one starts
+fn disassemble(gameboy: &GameBoy, address: u16, marker: &str) -> (String, u16) {
+    let line = &disasm::disassemble(gameboy, address, 1)[0];
+    let next = address.wrapping_add(line.bytes.len() as u16);
+    match &line.label {
+        Some(label) => (format!("  {}:\n{} {}", label, marker, line), next),
+        None => (format!("{} {}", marker, line), next),
+    }
+}
+
+fn set_register(gameboy: &mut GameBoy, name: &str, value: &str) -> Result<(), String> {
+    let regs = &mut gameboy.cpu.regs;
+    let value = parse_address(value)?;
+    let byte = || -> Result<u8, String> {
+        u8::try_from(value).map_err(|_| format!("{} is an 8 bit register", name))
+    };
+    match name.to_lowercase().as_str() {
+        "a" => regs.a = byte()?,
+        // the low nibble of F doesn't exist
+        "f" => regs.f = byte()? & 0xF0,
+        "b" => regs.b = byte()?,
+        "c" => regs.c = byte()?,
+        "d" => regs.d = byte()?,
+        "e" => regs.e = byte()?,
+        "h" => regs.h = byte()?,
+        "l" => regs.l = byte()?,
+        "af" => (regs.a, regs.f) = ((value >> 8) as u8, value as u8 & 0xF0),
+        "bc" => (regs.b, regs.c) = ((value >> 8) as u8, value as u8),
+        "de" => (regs.d, regs.e) = ((value >> 8) as u8, value as u8),
+        "hl" => (regs.h, regs.l) = ((value >> 8) as u8, value as u8),
+        "sp" => regs.sp = value,
+        "pc" => regs.pc = value,
+        _ => return Err(format!("No register {}", name)),
+    }
+    Ok(())
+}
+
+impl Debugger {
+    pub fn new(headless: bool) -> Self {
+        Debugger {
+            breakpoints: Vec::new(),
+            headless,
+            history: VecDeque::new(),
+            calls: Vec::new(),
+            last_command: String::new(),
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, address: u16, bank: Option<usize>) {
+        self.breakpoints.push(Breakpoint { address, bank });
+    }
+
+    pub fn add_watchpoint(&mut self, gameboy: &mut GameBoy, address: u16, access: Access) {
+        gameboy
+            .cpu
+            .bus
+            .watchpoints
+            .push(Watchpoint { address, access });
+    }
+
+    fn active(&self, gameboy: &GameBoy) -> bool {
+        !self.breakpoints.is_empty() || !gameboy.cpu.bus.watchpoints.is_empty()
+    }
+
+    // A frame for the frontend. Some when a breakpoint or watchpoint stopped
+    // it early, the rest of the frame runs on the next call.
+    pub fn run_frame(&mut self, gameboy: &mut GameBoy) -> Result<Option<Stop>, String> {
+        if !self.active(gameboy) {
+            // nothing follows the calls at full speed
+            self.calls.clear();
+            gameboy.run_frame()?;
+            return Ok(None);
+        }
+        match self.run(gameboy, Some(1), |_, _| false)? {
+            Stop::Frames => Ok(None),
+            stop => Ok(Some(stop)),
+        }
+    }
+
+    // Steps until `done` says so (it gets the opcode that just ran), a
+    // breakpoint or watchpoint hits or `frames` frames are over.
+    fn run(
+        &mut self,
+        gameboy: &mut GameBoy,
+        frames: Option<u32>,
+        mut done: impl FnMut(&GameBoy, u8) -> bool,
+    ) -> Result<Stop, String> {
+        let mut frames_left = frames;
+        loop {
+            // halted steps only tick the clock, there's no instruction to look at
+            let halted = gameboy.cpu.halted;
+            let pc = gameboy.cpu.regs.pc;
+            let sp = gameboy.cpu.regs.sp;
+            let ime = gameboy.cpu.int_master_enabled;
+            let bank = mapped_bank(&gameboy.cpu.bus, pc);
+            let opcode = gameboy.cpu.bus.read8_unwatched(pc as usize);
+            if !halted {
+                self.history.push_back(pc);
+                if self.history.len() > HISTORY_LENGTH {
+                    self.history.pop_front();
+                }
+            }
+            gameboy.step()?;
+            let call = Frame {
+                sp: sp.wrapping_sub(2),
+                site: pc,
+                bank,
+                interrupt: false,
+            };
+            self.follow_calls(gameboy, (!halted).then_some((opcode, call)), ime);
+
+            if let Some(hit) = gameboy.cpu.bus.watch_hit.take() {
+                return Ok(Stop::Watchpoint(hit));
+            }
+            if !halted && done(gameboy, opcode) {
+                return Ok(Stop::Done);
+            }
+            if !gameboy.cpu.halted {
+                let pc = gameboy.cpu.regs.pc;
+                let bank = mapped_bank(&gameboy.cpu.bus, pc);
+                if let Some(index) = self
+                    .breakpoints
+                    .iter()
+                    .position(|breakpoint| breakpoint.hit(pc, bank))
+                {
+                    return Ok(Stop::Breakpoint(index));
+                }
+            }
+            if gameboy.cpu.bus.ppu.have_update() {
+                // nobody plays the sound without a frontend
+                if self.headless {
+                    gameboy.audio_samples();
+                }
+                if let Some(left) = &mut frames_left {
+                    *left -= 1;
+                    if *left == 0 {
+                        return Ok(Stop::Frames);
+                    }
+                }
+            }
+        }
+    }
+
+    // Keeps the call stack up to date after a step. `call` is the frame the
+    // opcode that ran pushes if it was a taken CALL or RST, None for a halted
+    // step. IME going off without a DI means an interrupt got dispatched.
+    fn follow_calls(&mut self, gameboy: &GameBoy, call: Option<(u8, Frame)>, ime: bool) {
+        let cpu = &gameboy.cpu;
+        let interrupted =
+            ime && !cpu.int_master_enabled && call.is_none_or(|(opcode, _)| opcode != DI);
+        // SP as the instruction left it, before an interrupt pushed PC
+        let sp = if interrupted {
+            cpu.regs.sp.wrapping_add(2)
+        } else {
+            cpu.regs.sp
+        };
+        while self.calls.last().is_some_and(|frame| frame.sp < sp) {
+            self.calls.pop();
+        }
+        if let Some((opcode, frame)) = call {
+            let called = matches!(
+                Instruction::from_opcode(&opcode).instruction_type,
+                InstructionType::CALL | InstructionType::RST
+            );
+            if called && sp == frame.sp {
+                self.calls.push(frame);
+            }
+        }
+        if interrupted {
+            let sp = cpu.regs.sp;
+            let site = u16::from_le_bytes([
+                cpu.bus.read8_unwatched(sp as usize),
+                cpu.bus.read8_unwatched(sp.wrapping_add(1) as usize),
+            ]);
+            self.calls.push(Frame {
+                sp,
+                site,
+                bank: mapped_bank(&cpu.bus, site),
+                interrupt: true,
+            });
+        }
+        if self.calls.len() > CALL_STACK_LIMIT {
+            self.calls.remove(0);
+        }
+    }
+
+    pub fn step(&mut self, gameboy: &mut GameBoy, count: u32) -> Result<Stop, String> {
+        let mut left = count;
+        self.run(gameboy, None, |_, _| {
+            left -= 1;
+            left == 0
+        })
+    }
+
+    // steps over a CALL or RST, anything else is a plain step
+    pub fn next(&mut self, gameboy: &mut GameBoy) -> Result<Stop, String> {
+        let pc = gameboy.cpu.regs.pc;
+        let instruction = Instruction::from_opcode(&gameboy.cpu.bus.read8_unwatched(pc as usize));
+        if gameboy.cpu.halted
+            || !matches!(
+                instruction.instruction_type,
+                InstructionType::CALL | InstructionType::RST
+            )
+        {
+            return self.step(gameboy, 1);
+        }
+        let return_address = pc.wrapping_add(instruction.length as u16);
+        let sp = gameboy.cpu.regs.sp;
+        // the stack check keeps a recursive call from stopping too early
+        self.run(gameboy, None, |gameboy, _| {
+            gameboy.cpu.regs.pc == return_address && gameboy.cpu.regs.sp >= sp
+        })
+    }
+
+    // runs until a return pops the stack above where it is now
+    pub fn finish(&mut self, gameboy: &mut GameBoy) -> Result<Stop, String> {
+        let sp = gameboy.cpu.regs.sp;
+        self.run(gameboy, None, |gameboy, opcode| {
+            let returned = matches!(
+                Instruction::from_opcode(&opcode).instruction_type,
+                InstructionType::RET | InstructionType::RETI
+            );
+            returned && gameboy.cpu.regs.sp > sp
+        })
+    }
+
+    // Takes commands until the machine should run again. `stop` is why it
+    // stopped, if it did.
+    pub fn repl(&mut self, gameboy: &mut GameBoy, stop: Option<Stop>) -> Resume {
+        match stop {
+            Some(stop) => self.report(gameboy, stop),
+            None => self.show_location(gameboy),
+        }
+        let stdin = io::stdin();
+        loop {
+            print!("(gb) ");
+            _ = io::stdout().flush();
+            let mut line = String::new();
+            match stdin.lock().read_line(&mut line) {
+                Ok(0) | Err(_) => return Resume::Quit,
+                Ok(_) => {}
+            }
+            // an empty line repeats the last command, handy for stepping
+            let line = line.trim();
+            if !line.is_empty() {
+                self.last_command = line.to_string();
+            }
+            let command = self.last_command.clone();
+            match self.command(gameboy, &command) {
+                Ok(Some(resume)) => return resume,
+                Ok(None) => {}
+                Err(err) => println!("{}", err),
+            }
+        }
+    }
+
+    // None keeps the prompt open
+    fn command(&mut self, gameboy: &mut GameBoy, line: &str) -> Result<Option<Resume>, String> {
+        let mut words = line.split_whitespace();
+        let Some(name) = words.next() else {
+            return Ok(None);
+        };
+        let args: Vec<&str> = words.collect();
+        match name {
+            "help" | "h" | "?" => println!("{}", HELP),
+            "step" | "s" => {
+                let stop = self.step(gameboy, parse_count(args.first(), 1)?)?;
+                self.report(gameboy, stop);
+            }
+            "next" | "n" => {
+                let stop = self.next(gameboy)?;
+                self.report(gameboy, stop);
+            }
+            "finish" | "f" => {
+                let stop = self.finish(gameboy)?;
+                self.report(gameboy, stop);
+            }
+            "continue" | "c" => {
+                if !self.headless {
+                    return Ok(Some(Resume::Continue));
+                }
+                let frames = match args.first() {
+                    Some(_) => Some(parse_count(args.first(), 1)?),
+                    None => None,
+                };
+                let stop = self.run(gameboy, frames, |_, _| false)?;
+                self.report(gameboy, stop);
+            }
+            "break" | "b" => match args.first() {
+                Some(target) => {
+                    let (bank, address) = parse_target(gameboy, target)?;
+                    self.add_breakpoint(address, bank);
+                    println!("Breakpoint {} at {}", self.breakpoints.len(), target);
+                }
+                None => self.list(gameboy),
+            },
+            "delete" => match args.first() {
+                Some(number) => {
+                    let index = parse_count(Some(number), 1)? as usize - 1;
+                    if index >= self.breakpoints.len() {
+                        return Err(format!("No breakpoint {}", number));
+                    }
+                    self.breakpoints.remove(index);
+                }
+                None => self.breakpoints.clear(),
+            },
+            "watch" | "w" => {
+                let (_, address) =
+                    parse_target(gameboy, args.first().ok_or("watch needs an address")?)?;
+                let access = match args.get(1) {
+                    Some(name) => Access::from_name(name)
+                        .ok_or_else(|| format!("Not r, w or rw: {}", name))?,
+                    None => Access::ReadWrite,
+                };
+                self.add_watchpoint(gameboy, address, access);
+                println!(
+                    "Watchpoint {} at {:04X}",
+                    gameboy.cpu.bus.watchpoints.len(),
+                    address
+                );
+            }
+            "unwatch" => {
+                let watchpoints = &mut gameboy.cpu.bus.watchpoints;
+                match args.first() {
+                    Some(number) => {
+                        let index = parse_count(Some(number), 1)? as usize - 1;
+                        if index >= watchpoints.len() {
+                            return Err(format!("No watchpoint {}", number));
+                        }
+                        watchpoints.remove(index);
+                    }
+                    None => watchpoints.clear(),
+                }
+            }
+            "info" | "i" => self.list(gameboy),
+            "regs" | "r" => self.show_registers(gameboy),
+            "set" => {
+                let (Some(register), Some(value)) = (args.first(), args.get(1)) else {
+                    return Err("set <reg> <value>".to_string());
+                };
+                set_register(gameboy, register, value)?;
+                self.show_registers(gameboy);
+            }
+            "mem" | "x" => {
+                let (_, address) =
+                    parse_target(gameboy, args.first().ok_or("mem needs an address")?)?;
+                let count = parse_count(args.get(1), 64)?.min(0x10000 - address as u32);
+                let bus = &gameboy.cpu.bus;
+                let bytes: Vec<u8> = (0..count)
+                    .map(|offset| bus.read8_unwatched(address as usize + offset as usize))
+                    .collect();
+                dump(address as usize, &bytes);
+            }
+            "peek" => {
+                let domain = parse_domain(args.first())?;
+                let (bank, offset) =
+                    parse_domain_target(args.get(1).ok_or("peek needs an offset")?)?;
+                // a bad offset still gets read once for its error
+                let count = (parse_count(args.get(2), 64)? as usize)
+                    .min(domain.bank_size().saturating_sub(offset))
+                    .max(1);
+                let bytes = (offset..offset + count)
+                    .map(|offset| gameboy.cpu.bus.domain_read(domain, bank, offset))
+                    .collect::<Result<Vec<u8>, String>>()?;
+                dump(offset, &bytes);
+            }
+            "poke" => {
+                let domain = parse_domain(args.first())?;
+                let (bank, offset) =
+                    parse_domain_target(args.get(1).ok_or("poke needs an offset")?)?;
+                let bytes = args[2..]
+                    .iter()
+                    .map(|byte| parse_byte(byte))
+                    .collect::<Result<Vec<u8>, String>>()?;
+                for (index, byte) in bytes.iter().enumerate() {
+                    gameboy
+                        .cpu
+                        .bus
+                        .domain_write(domain, bank, offset + index, *byte)?;
+                }
+            }
+            "write" => {
+                let (_, address) =
+                    parse_target(gameboy, args.first().ok_or("write needs an address")?)?;
+                let bytes = args[1..]
+                    .iter()
+                    .map(|byte| parse_byte(byte))
+                    .collect::<Result<Vec<u8>, String>>()?;
+                for (offset, byte) in bytes.iter().enumerate() {
+                    let address = address.wrapping_add(offset as u16);
+                    gameboy.cpu.bus.write8(address as usize, *byte);
+                }
+                // our own writes don't count as hits
+                gameboy.cpu.bus.watch_hit.take();
+            }
+            "disasm" | "d" => match args.first() {
+                Some(address) => {
+                    let (_, mut address) = parse_target(gameboy, address)?;
+                    for _ in 0..parse_count(args.get(1), 10)? {
+                        let (line, next) = disassemble(gameboy, address, " ");
+                        println!("{}", line);
+                        address = next;
+                    }
+                }
+                None => self.disassemble_around_pc(gameboy),
+            },
+            "backtrace" | "bt" => self.backtrace(gameboy),
+            "quit" | "q" => return Ok(Some(Resume::Quit)),
+            _ => return Err(format!("Unknown command {}, try help", name)),
+        }
+        Ok(None)
+    }
+
+    fn report(&self, gameboy: &GameBoy, stop: Stop) {
+        match stop {
+            Stop::Breakpoint(index) => println!(
+                "Breakpoint {} at {}",
+                index + 1,
+                location(gameboy, gameboy.cpu.regs.pc)
+            ),
+            Stop::Watchpoint(hit) => {
+                let access = if hit.write { "Write" } else { "Read" };
+                let by = match self.history.back() {
+                    Some(pc) => format!(" by {}", location(gameboy, *pc)),
+                    None => String::new(),
+                };
+                println!(
+                    "{} of {:02X} at {:04X}{}",
+                    access, hit.value, hit.address, by
+                );
+            }
+            Stop::Done | Stop::Frames => {}
+        }
+        self.show_location(gameboy);
+    }
+
+    fn show_location(&self, gameboy: &GameBoy) {
+        self.show_registers(gameboy);
+        let (line, _) = disassemble(gameboy, gameboy.cpu.regs.pc, ">");
+        println!("{}", line);
+    }
+
+    fn show_registers(&self, gameboy: &GameBoy) {
+        let cpu = &gameboy.cpu;
+        let regs = &cpu.regs;
+        println!(
+            "A:{:02X} F:{:02X} [{}] B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} IME:{} HALT:{} cycles:{}",
+            regs.a,
+            regs.f,
+            regs.get_flags_mnemonic(),
+            regs.b,
+            regs.c,
+            regs.d,
+            regs.e,
+            regs.h,
+            regs.l,
+            regs.sp,
+            regs.pc,
+            cpu.int_master_enabled as u8,
+            cpu.halted as u8,
+            cpu.cycles
+        );
+    }
+
+    // what ran last, then PC and what comes after it
+    fn disassemble_around_pc(&self, gameboy: &GameBoy) {
+        for pc in &self.history {
+            let (line, _) = disassemble(gameboy, *pc, " ");
+            println!("{}", line);
+        }
+        let mut address = gameboy.cpu.regs.pc;
+        for index in 0..DISASSEMBLY_AHEAD {
+            let (line, next) = disassemble(gameboy, address, if index == 0 { ">" } else { " " });
+            println!("{}", line);
+            address = next;
+        }
+    }
+
+    // innermost first, #0 is where PC is now
+    fn backtrace(&self, gameboy: &GameBoy) {
+        println!("#0  {}", location(gameboy, gameboy.cpu.regs.pc));
+        for (depth, frame) in self.calls.iter().rev().enumerate() {
+            let how = if frame.interrupt {
+                "interrupted"
+            } else {
+                "called from"
+            };
+            println!(
+                "#{}  {} {}",
+                depth + 1,
+                how,
+                describe(gameboy, frame.site, frame.bank)
+            );
+        }
+    }
+
+    fn list(&self, gameboy: &GameBoy) {
+        for (index, breakpoint) in self.breakpoints.iter().enumerate() {
+            let address = breakpoint.address;
+            let bank = breakpoint.bank.or((address < 0x4000).then_some(0));
+            println!(
+                "breakpoint {}: {}",
+                index + 1,
+                describe(gameboy, address, bank).trim_start()
+            );
+        }
+        for (index, watchpoint) in gameboy.cpu.bus.watchpoints.iter().enumerate() {
+            println!(
+                "watchpoint {}: {:04X} {:?}",
+                index + 1,
+                watchpoint.address,
+                watchpoint.access
+            );
+        }
+    }
+}
@@ -0,0 +1,349 @@
+use core::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::bus::Bus;
+use crate::cartridge::Cartridge;
+use crate::instruction::{AddressMode, ConditionType, Instruction, InstructionType, RegisterType};
+use crate::symbols::Symbols;
+use crate::GameBoy;
+
+static ROM_BANK_SIZE: usize = 0x4000;
+
+// One decoded instruction
+pub struct Line {
+    // the ROM bank it was read from, None outside of ROM
+    pub bank: Option<usize>,
+    pub address: u16,
+    // the label defined right here
+    pub label: Option<String>,
+    pub bytes: Vec<u8>,
+    // RGBDS syntax, e.g. `ld a, [$FF44]`
+    pub text: String,
+}
+
+// 01:4000  FA 44 FF  ld a, [$FF44]
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes: Vec<String> = self
+            .bytes
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        match self.bank {
+            Some(bank) => write!(f, "{:02X}:{:04X}", bank, self.address)?,
+            None => write!(f, "   {:04X}", self.address)?,
+        }
+        write!(f, "  {: <8}  {}", bytes.join(" "), self.text)
+    }
+}
+
+fn register(register: &RegisterType) -> String {
+    register.to_string().to_lowercase()
+}
+
+fn signed(value: u8) -> String {
+    match value as i8 {
+        value if value < 0 => format!("-${:02X}", -(value as i16)),
+        value => format!("${:02X}", value),
+    }
+}
+
+// `jp nz, $0150`, `ret z`, `jr $0150` ...
+fn conditional(mnemonic: &str, condition: &ConditionType, operand: Option<String>) -> String {
+    let condition = match condition {
+        ConditionType::NONE => None,
+        condition => Some(condition.to_string().to_lowercase()),
+    };
+    match (condition, operand) {
+        (Some(condition), Some(operand)) => format!("{} {}, {}", mnemonic, condition, operand),
+        (Some(condition), None) => format!("{} {}", mnemonic, condition),
+        (None, Some(operand)) => format!("{} {}", mnemonic, operand),
+        (None, None) => mnemonic.to_string(),
+    }
+}
+
+// the operation a CB prefix selects
+fn decode_cb(opcode: u8) -> String {
+    let target = match RegisterType::decode((opcode & 0x07) as usize) {
+        RegisterType::HL => "[hl]".to_string(),
+        target => register(&target),
+    };
+    let bit = (opcode >> 3) & 0x07;
+    match opcode >> 6 {
+        0 => {
+            let mnemonic = ["rlc", "rrc", "rl", "rr", "sla", "sra", "swap", "srl"][bit as usize];
+            format!("{} {}", mnemonic, target)
+        }
+        1 => format!("bit {}, {}", bit, target),
+        2 => format!("res {}, {}", bit, target),
+        _ => format!("set {}, {}", bit, target),
+    }
+}
+
+// Decodes the instruction `bytes` starts with, `address` is where it sits so
+// relative jumps can show their target. Returns the text and the length, an
+// instruction cut off by the end of `bytes` comes out as a single `db`.
+pub fn decode(bytes: &[u8], address: u16) -> (String, usize) {
+    decode_labeled(bytes, address, |_| None)
+}
+
+// decode() with jump targets and memory operands named by `label` where it
+// knows them
+pub fn decode_labeled(
+    bytes: &[u8],
+    address: u16,
+    label: impl Fn(u16) -> Option<String>,
+) -> (String, usize) {
+    let Some(&opcode) = bytes.first() else {
+        return (String::new(), 0);
+    };
+    let instruction = Instruction::from_opcode(&opcode);
+    let length = instruction.length as usize;
+    if bytes.len() < length || instruction.instruction_type == InstructionType::NONE {
+        return (format!("db ${:02X}", opcode), 1);
+    }
+    let n8 = bytes.get(1).copied().unwrap_or(0);
+    let n16 = u16::from_le_bytes([n8, bytes.get(2).copied().unwrap_or(0)]);
+    let mnemonic = instruction.instruction_type.to_string().to_lowercase();
+    let r1 = register(&instruction.register_1);
+    let r2 = register(&instruction.register_2);
+    let target = |address: u16| label(address).unwrap_or_else(|| format!("${:04X}", address));
+    let high = |offset: u8| {
+        let address = 0xFF00 | offset as u16;
+        label(address).unwrap_or_else(|| format!("${:04X}", address))
+    };
+
+    let text = match (&instruction.instruction_type, &instruction.address_mode) {
+        (InstructionType::CB, _) => decode_cb(n8),
+        // the byte after STOP is padding, RGBDS adds it by itself
+        (InstructionType::STOP, _) => mnemonic,
+        (InstructionType::RST, _) => format!("rst ${:02X}", instruction.rst_vec),
+        (InstructionType::JR, _) => {
+            let destination = address.wrapping_add(2).wrapping_add(n8 as i8 as u16);
+            conditional(&mnemonic, &instruction.condition, Some(target(destination)))
+        }
+        (_, AddressMode::IMPLIED) => conditional(&mnemonic, &instruction.condition, None),
+        (_, AddressMode::D16) => conditional(&mnemonic, &instruction.condition, Some(target(n16))),
+        (_, AddressMode::D8) => format!("{} ${:02X}", mnemonic, n8),
+        (_, AddressMode::R) => format!("{} {}", mnemonic, r1),
+        (_, AddressMode::MR) => format!("{} [{}]", mnemonic, r1),
+        (_, AddressMode::R_R) => format!("{} {}, {}", mnemonic, r1, r2),
+        (_, AddressMode::R_MR) if instruction.register_2 == RegisterType::C => {
+            format!("ldh {}, [c]", r1)
+        }
+        (_, AddressMode::R_MR) => format!("{} {}, [{}]", mnemonic, r1, r2),
+        (_, AddressMode::MR_R) if instruction.register_1 == RegisterType::C => {
+            format!("ldh [c], {}", r2)
+        }
+        (_, AddressMode::MR_R) => format!("{} [{}], {}", mnemonic, r1, r2),
+        (_, AddressMode::R_HLI) => format!("{} {}, [hl+]", mnemonic, r1),
+        (_, AddressMode::R_HLD) => format!("{} {}, [hl-]", mnemonic, r1),
+        (_, AddressMode::HLI_R) => format!("{} [hl+], {}", mnemonic, r2),
+        (_, AddressMode::HLD_R) => format!("{} [hl-], {}", mnemonic, r2),
+        (_, AddressMode::R_D8) if instruction.register_1 == RegisterType::SP => {
+            format!("{} sp, {}", mnemonic, signed(n8))
+        }
+        (_, AddressMode::R_D8) => format!("{} {}, ${:02X}", mnemonic, r1, n8),
+        (_, AddressMode::MR_D8) => format!("{} [{}], ${:02X}", mnemonic, r1, n8),
+        (_, AddressMode::R_A8) => format!("{} {}, [{}]", mnemonic, r1, high(n8)),
+        (_, AddressMode::A8_R) => format!("{} [{}], {}", mnemonic, high(n8), r2),
+        (_, AddressMode::HL_SPR) => match n8 as i8 {
+            offset if offset < 0 => format!("ld hl, sp-${:02X}", -(offset as i16)),
+            offset => format!("ld hl, sp+${:02X}", offset),
+        },
+        (_, AddressMode::R_D16) => format!("{} {}, ${:04X}", mnemonic, r1, n16),
+        (_, AddressMode::R_A16) => format!("{} {}, [{}]", mnemonic, r1, target(n16)),
+        (_, AddressMode::A16_R) => format!("{} [{}], {}", mnemonic, target(n16), r2),
+        (_, AddressMode::D16_R) => format!("{} ${:04X}, {}", mnemonic, n16, r2),
+    };
+    (text, length)
+}
+
+// the ROM bank the MBC has mapped at `address`, None outside of ROM
+pub fn mapped_bank(bus: &Bus, address: u16) -> Option<usize> {
+    match address {
+        0..0x4000 => Some(0),
+        0x4000..0x8000 => bus.cart.as_ref().map(|cartridge| cartridge.rom_bank()),
+        _ => None,
+    }
+}
+
+// An operand's label. Code in a switchable bank mostly jumps within its own
+// bank, `romx_bank` is the bank to look up 4000-7FFF in.
+fn operand_label(symbols: &Symbols, address: u16, romx_bank: Option<usize>) -> Option<String> {
+    let bank = match address {
+        0x4000..0x8000 => romx_bank,
+        _ => None,
+    };
+    symbols.label(address, bank).map(str::to_string)
+}
+
+// `count` instructions from `address` as the CPU sees memory right now
+pub fn disassemble(gameboy: &GameBoy, address: u16, count: usize) -> Vec<Line> {
+    let bus = &gameboy.cpu.bus;
+    let symbols = &gameboy.symbols;
+    let romx_bank = mapped_bank(bus, 0x4000);
+    let mut address = address;
+    let mut lines = Vec::new();
+    for _ in 0..count {
+        let bytes: Vec<u8> = (0..3)
+            .map(|offset| bus.read8_unwatched(address.wrapping_add(offset) as usize))
+            .collect();
+        let (text, length) = decode_labeled(&bytes, address, |operand| {
+            operand_label(symbols, operand, romx_bank)
+        });
+        let bank = mapped_bank(bus, address);
+        lines.push(Line {
+            bank,
+            address,
+            label: symbols.label(address, bank).map(str::to_string),
+            bytes: bytes[..length].to_vec(),
+            text,
+        });
+        address = address.wrapping_add(length as u16);
+    }
+    lines
+}
+
+// A whole ROM bank from start to end, whether it is mapped or not. Data
+// decodes as instructions too, there's no telling them apart.
+pub fn disassemble_bank(cartridge: &Cartridge, bank: usize, symbols: &Symbols) -> Vec<Line> {
+    let data: Vec<u8> = (0..ROM_BANK_SIZE)
+        .map(|offset| cartridge.read_rom(bank, offset))
+        .collect();
+    // bank 0 sits at 0000, every other bank gets switched in at 4000
+    let base = if bank == 0 { 0 } else { ROM_BANK_SIZE };
+    // bank 0 can't know what is switched in
+    let romx_bank = (bank != 0).then_some(bank);
+    let mut offset = 0;
+    let mut lines = Vec::new();
+    while offset < data.len() {
+        let address = (base + offset) as u16;
+        let (text, length) = decode_labeled(&data[offset..], address, |operand| {
+            operand_label(symbols, operand, romx_bank)
+        });
+        lines.push(Line {
+            bank: Some(bank),
+            address,
+            label: symbols.label(address, Some(bank)).map(str::to_string),
+            bytes: data[offset..offset + length].to_vec(),
+            text,
+        });
+        offset += length;
+    }
+    lines
+}
+
+// Writes banks `first` to `last` as RGBDS source, one SECTION per bank with
+// the address and bytes of every instruction in a comment
+pub fn write_banks(
+    cartridge: &Cartridge,
+    first: usize,
+    last: usize,
+    path: &str,
+    symbols: &Symbols,
+) -> Result<(), String> {
+    let bank_count = cartridge.info.rom_bank_count;
+    if first > last || last >= bank_count {
+        return Err(format!(
+            "Can't disassemble banks {:02X}-{:02X}, the ROM has banks 00-{:02X}",
+            first,
+            last,
+            bank_count - 1
+        ));
+    }
+    let file = File::create(path).map_err(|err| format!("Couldn't create {}: {}", path, err))?;
+    let mut writer = BufWriter::new(file);
+    let mut output = format!("; {}\n", cartridge.info.title.trim_end_matches('\0'));
+    for bank in first..=last {
+        if bank == 0 {
+            output.push_str("\nSECTION \"ROM Bank $00\", ROM0[$0000]\n\n");
+        } else {
+            output.push_str(&format!(
+                "\nSECTION \"ROM Bank ${:02X}\", ROMX[$4000], BANK[${:02X}]\n\n",
+                bank, bank
+            ));
+        }
+        for line in disassemble_bank(cartridge, bank, symbols) {
+            if let Some(label) = &line.label {
+                output.push_str(&format!("{}:\n", label));
+            }
+            let bytes: Vec<String> = line
+                .bytes
+                .iter()
+                .map(|byte| format!("{:02X}", byte))
+                .collect();
+            output.push_str(&format!(
+                "    {: <24}; ${:04X}: {}\n",
+                line.text,
+                line.address,
+                bytes.join(" ")
+            ));
+        }
+        writer
+            .write_all(output.as_bytes())
+            .map_err(|err| err.to_string())?;
+        output.clear();
+    }
+    writer.flush().map_err(|err| err.to_string())
+}
+
+// Disassembles a ROM file to `path`, every bank when `banks` is None. The
+// labels come from a symbol file next to the ROM, when there is one.
+pub fn write_rom(rom_path: &str, banks: Option<(usize, usize)>, path: &str) -> Result<(), String> {
+    let cartridge = Cartridge::from_path(rom_path)?;
+    let symbols = Symbols::for_rom(rom_path).unwrap_or_default();
+    let (first, last) = banks.unwrap_or((0, cartridge.info.rom_bank_count - 1));
+    write_banks(&cartridge, first, last, path, &symbols)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodings() {
+        let table: &[(&[u8], &str, usize)] = &[
+            (&[0xE8, 0xFE], "add sp, -$02", 2),
+            (&[0xF8, 0x05], "ld hl, sp+$05", 2),
+            (&[0xE2], "ldh [c], a", 1),
+            (&[0xF2], "ldh a, [c]", 1),
+            (&[0xE0, 0x44], "ldh [$FF44], a", 2),
+            (&[0x22], "ld [hl+], a", 1),
+            (&[0x3A], "ld a, [hl-]", 1),
+            (&[0xFA, 0x34, 0x12], "ld a, [$1234]", 3),
+            (&[0xCB, 0x46], "bit 0, [hl]", 2),
+            (&[0xCB, 0x36], "swap [hl]", 2),
+            (&[0xCB, 0xFE], "set 7, [hl]", 2),
+            (&[0x10, 0x00], "stop", 2),
+            (&[0xD3], "db $D3", 1),
+            (&[0xFD], "db $FD", 1),
+            // cut off by the end of the bytes
+            (&[0xC3, 0x50], "db $C3", 1),
+        ];
+        for (bytes, text, length) in table {
+            assert_eq!(
+                decode(bytes, 0x150),
+                (text.to_string(), *length),
+                "{:02X?}",
+                bytes
+            );
+        }
+    }
+
+    #[test]
+    fn relative_jumps() {
+        // relative to the end of the 2 byte instruction at 0150
+        assert_eq!(decode(&[0x18, 0xFE], 0x150).0, "jr $0150");
+        assert_eq!(decode(&[0x20, 0x05], 0x150).0, "jr nz, $0157");
+        assert_eq!(decode(&[0x38, 0x80], 0x150).0, "jr c, $00D2");
+        assert_eq!(
+            decode_labeled(&[0x18, 0x05], 0x150, |address| {
+                (address == 0x157).then(|| "Loop".to_string())
+            })
+            .0,
+            "jr Loop"
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use crate::state::{SaveState, StateReader, StateWriter};
+
 pub struct DMA {
     pub active: bool,
     pub byte_address: usize,
@@ -42,3 +44,202 @@ impl DMA {
         self.active
     }
 }
+
+impl SaveState for DMA {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bool(self.active);
+        writer.write_usize(self.byte_address);
+        writer.write_u8(self.value);
+        writer.write_u8(self.start_delay);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.active = reader.read_bool()?;
+        self.byte_address = reader.read_usize()?;
+        self.value = reader.read_u8()?;
+        self.start_delay = reader.read_u8()?;
+        Ok(())
+    }
+}
+
+// CGB VRAM DMA (FF51 - FF55). Copies 16 byte blocks into VRAM, either all at
+// once (general purpose) or one block per HBlank.
+pub struct HDMA {
+    pub source: u16,
+    pub dest: u16,
+    pub blocks_left: u8,
+    pub hblank_mode: bool,
+    pub active: bool,
+}
+impl Default for HDMA {
+    fn default() -> Self {
+        HDMA::new()
+    }
+}
+
+impl HDMA {
+    pub fn new() -> HDMA {
+        HDMA {
+            source: 0,
+            dest: 0,
+            blocks_left: 0,
+            hblank_mode: false,
+            active: false,
+        }
+    }
+
+    // only HDMA5 can be read back: bit 7 is 0 while a transfer is running,
+    // the rest is the remaining block count - 1 (0xFF once it's done)
+    pub fn read(&self, address: usize) -> u8 {
+        match address {
+            0xFF55 => ((!self.active as u8) << 7) | (self.blocks_left.wrapping_sub(1) & 0x7F),
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, address: usize, value: u8) {
+        match address {
+            0xFF51 => self.source = (self.source & 0x00FF) | ((value as u16) << 8),
+            0xFF52 => self.source = (self.source & 0xFF00) | (value & 0xF0) as u16,
+            0xFF53 => self.dest = (self.dest & 0x00FF) | (((value & 0x1F) as u16) << 8),
+            0xFF54 => self.dest = (self.dest & 0xFF00) | (value & 0xF0) as u16,
+            0xFF55 => {
+                // writing bit 7 = 0 during an HBlank transfer cancels it
+                if self.active && self.hblank_mode && value & 0x80 == 0 {
+                    self.active = false;
+                    return;
+                }
+                self.blocks_left = (value & 0x7F) + 1;
+                self.hblank_mode = value & 0x80 > 0;
+                self.active = true;
+            }
+            _ => {}
+        }
+    }
+
+    // Like write, but a transfer is never started or cancelled, FF55 only
+    // sets how many blocks are left
+    pub fn poke(&mut self, address: usize, value: u8) {
+        match address {
+            0xFF55 => self.blocks_left = (value & 0x7F) + 1,
+            _ => self.write(address, value),
+        }
+    }
+
+    // source and VRAM destination of the next block
+    pub fn next_block(&mut self) -> Option<(usize, usize)> {
+        if !self.active {
+            return None;
+        }
+        let block = (self.source as usize, 0x8000 + (self.dest as usize & 0x1FF0));
+        self.source = self.source.wrapping_add(0x10);
+        self.dest = self.dest.wrapping_add(0x10) & 0x1FF0;
+        self.blocks_left -= 1;
+        self.active = self.blocks_left > 0;
+        Some(block)
+    }
+}
+
+impl SaveState for HDMA {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u16(self.source);
+        writer.write_u16(self.dest);
+        writer.write_u8(self.blocks_left);
+        writer.write_bool(self.hblank_mode);
+        writer.write_bool(self.active);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.source = reader.read_u16()?;
+        self.dest = reader.read_u16()?;
+        self.blocks_left = reader.read_u8()?;
+        self.hblank_mode = reader.read_bool()?;
+        self.active = reader.read_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::Bus;
+    use crate::memory::MemoryDomain;
+
+    // source C000, destination 8100
+    fn cgb_bus() -> Bus {
+        let mut bus = Bus::new();
+        bus.set_cgb_mode(true);
+        for i in 0..0x40 {
+            bus.write8(0xC000 + i, i as u8 + 1);
+        }
+        bus.write8(0xFF51, 0xC0);
+        bus.write8(0xFF52, 0x00);
+        bus.write8(0xFF53, 0x01);
+        bus.write8(0xFF54, 0x00);
+        bus
+    }
+
+    fn vram(bus: &Bus, offset: usize) -> u8 {
+        bus.domain_read(MemoryDomain::Vram, 0, offset).unwrap()
+    }
+
+    #[test]
+    fn readback() {
+        let mut hdma = HDMA::new();
+        assert_eq!(hdma.read(0xFF55), 0xFF);
+        hdma.write(0xFF55, 0x83);
+        assert_eq!(hdma.read(0xFF55), 0x03);
+        assert_eq!(hdma.next_block(), Some((0x0000, 0x8000)));
+        assert_eq!(hdma.read(0xFF55), 0x02);
+        for _ in 0..3 {
+            hdma.next_block();
+        }
+        assert_eq!(hdma.read(0xFF55), 0xFF);
+        assert_eq!(hdma.next_block(), None);
+    }
+
+    #[test]
+    fn hblank_cancel() {
+        let mut hdma = HDMA::new();
+        hdma.write(0xFF55, 0x85);
+        hdma.next_block();
+        hdma.next_block();
+        hdma.write(0xFF55, 0x00);
+        // bit 7 set, 4 blocks left
+        assert_eq!(hdma.read(0xFF55), 0x83);
+        assert_eq!(hdma.next_block(), None);
+    }
+
+    #[test]
+    fn general_purpose_in_write() {
+        let mut bus = cgb_bus();
+        bus.write8(0xFF55, 0x01);
+        // done before the write returns
+        assert_eq!(bus.read8(0xFF55), 0xFF);
+        assert_eq!(bus.hdma_stall, 16);
+        for i in 0..0x20 {
+            assert_eq!(vram(&bus, 0x100 + i), i as u8 + 1);
+        }
+        assert_eq!(vram(&bus, 0x120), 0);
+    }
+
+    #[test]
+    fn hblank_block_at_a_time() {
+        let mut bus = cgb_bus();
+        bus.write8(0xFF55, 0x81);
+        assert_eq!(bus.hdma_stall, 0);
+        assert_eq!(vram(&bus, 0x100), 0);
+
+        bus.hdma_hblank();
+        assert_eq!(bus.hdma_stall, 8);
+        assert_eq!(bus.read8(0xFF55), 0x00);
+        assert_eq!(vram(&bus, 0x10F), 0x10);
+        assert_eq!(vram(&bus, 0x110), 0);
+
+        bus.hdma_hblank();
+        bus.hdma_hblank();
+        assert_eq!(bus.hdma_stall, 16);
+        assert_eq!(bus.read8(0xFF55), 0xFF);
+        assert_eq!(vram(&bus, 0x11F), 0x20);
+    }
+}
@@ -0,0 +1,382 @@
+use crate::audio::AudioOutput;
+use crate::bgb::{BgbLink, LinkTarget};
+use crate::cartridge::Cartridge;
+use crate::cheats::Cheats;
+use crate::debugger::{Debugger, Resume, Stop};
+use crate::link::LinkedPair;
+use crate::model::Model;
+use crate::rendering::{KeyMap, Renderer};
+use crate::rewind::Rewind;
+use crate::symbols::Symbols;
+use crate::trace::TraceOptions;
+use crate::GameBoy;
+use fps_counter::FPSCounter;
+use std::fs;
+use std::io::{self, Write};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// What the emulation loop waits on between frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncMode {
+    // sleep to the GameBoy refresh rate
+    Timer,
+    // wait for the audio queue to drain down to the target latency
+    Audio,
+    // let the renderer block on the display refresh, assumes a ~60Hz display
+    Vsync,
+}
+
+impl SyncMode {
+    pub fn from_name(name: &str) -> Option<SyncMode> {
+        match name {
+            "timer" => Some(SyncMode::Timer),
+            "audio" => Some(SyncMode::Audio),
+            "vsync" => Some(SyncMode::Vsync),
+            _ => None,
+        }
+    }
+}
+
+pub struct GBCore {
+    pub paused: Arc<AtomicBool>,
+    pub running: bool,
+    pub rewind_interval: u32,
+    pub rewind_budget: usize,
+    pub sync: SyncMode,
+    pub audio_latency: Duration,
+    // picked from the cartridge header when not set
+    pub model: Option<Model>,
+    pub boot_rom: Option<String>,
+    // another emulator to link up with over the BGB protocol
+    pub remote_link: Option<LinkTarget>,
+    // file to write a Gameboy Doctor trace to
+    pub trace: Option<String>,
+    pub trace_options: TraceOptions,
+    // LY always reads 0x90, which Gameboy Doctor logs assume
+    pub doctor: bool,
+    // symbol file, game.sym or game.noi next to the ROM get picked up without it
+    pub symbols: Option<String>,
+}
+
+// about five minutes of emulated time, test ROMs are done long before that
+static HEADLESS_FRAME_LIMIT: u32 = 60 * 60 * 5;
+
+impl GBCore {
+    pub fn new() -> GBCore {
+        GBCore {
+            paused: Arc::new(AtomicBool::new(false)),
+            running: true,
+            rewind_interval: 2,
+            rewind_budget: 64 * 1024 * 1024,
+            sync: SyncMode::Audio,
+            audio_latency: Duration::from_millis(60),
+            model: None,
+            boot_rom: None,
+            remote_link: None,
+            trace: None,
+            trace_options: TraceOptions::default(),
+            doctor: false,
+            symbols: None,
+        }
+    }
+
+    fn load(&self, path: &str) -> Result<GameBoy, String> {
+        let cartridge = Cartridge::from_path(path)?;
+        println!("{}", cartridge.info.to_string());
+        let model = self
+            .model
+            .unwrap_or_else(|| Model::for_cartridge(&cartridge.info));
+        match &self.boot_rom {
+            Some(boot_rom_path) => {
+                let boot_rom = fs::read(boot_rom_path).map_err(|err| err.to_string())?;
+                GameBoy::with_boot_rom(cartridge, model, boot_rom)
+            }
+            None => Ok(GameBoy::with_model(cartridge, model)),
+        }
+    }
+
+    // a single machine with the link and debugging options applied
+    fn prepare(&self, path: &str) -> Result<GameBoy, String> {
+        let mut gameboy = self.load(path)?;
+        if let Some(target) = &self.remote_link {
+            if let LinkTarget::Listen(address) = target {
+                println!("Waiting for a link connection on {}", address);
+            }
+            let link = BgbLink::open(target)?;
+            if let Some(peer) = link.peer_address() {
+                println!("Linked with {}", peer);
+            }
+            gameboy.connect_link(Box::new(link));
+        }
+        let symbols = match &self.symbols {
+            Some(symbols_path) => Some(Symbols::load(symbols_path)?),
+            None => Symbols::for_rom(path),
+        };
+        if let Some(symbols) = symbols {
+            println!("{} symbols", symbols.len());
+            gameboy.set_symbols(symbols);
+        }
+        // saves/game.cht, a broken one shouldn't keep the game from starting
+        let cheats = gameboy
+            .cpu
+            .bus
+            .cart
+            .as_ref()
+            .map(|cart| Cheats::for_rom(&cart.info));
+        match cheats {
+            Some(Ok(cheats)) => {
+                if !cheats.is_empty() {
+                    println!("{} cheats", cheats.len());
+                }
+                gameboy.set_cheats(cheats);
+            }
+            Some(Err(err)) => println!("No cheats: {}", err),
+            None => {}
+        }
+        if let Some(trace) = &self.trace {
+            let mut options = self.trace_options.clone();
+            // labels would throw off Gameboy Doctor
+            options.labels = !self.doctor && !gameboy.symbols.is_empty();
+            gameboy.start_trace(trace, options)?;
+        }
+        if self.doctor {
+            gameboy.set_ly_override(Some(0x90));
+        }
+        Ok(gameboy)
+    }
+
+    pub fn start(&mut self, path: &str) -> Result<(), String> {
+        let mut gameboy = self.prepare(path)?;
+        // a rewound machine would run out of sync with the peer
+        let can_rewind = self.remote_link.is_none();
+
+        let mut ui = Renderer::new(self.sync == SyncMode::Vsync);
+        let mut rewind = Rewind::new(self.rewind_interval, self.rewind_budget);
+        let mut debugger = Debugger::new(false);
+
+        let mut audio = match ui
+            .audio()
+            .and_then(|subsystem| AudioOutput::new(&subsystem, self.audio_latency))
+        {
+            Ok(audio) => {
+                gameboy.set_audio_sample_rate(audio.device_rate());
+                Some(audio)
+            }
+            Err(err) => {
+                println!("No audio: {}", err);
+                None
+            }
+        };
+        let mut sync = self.sync;
+        if sync == SyncMode::Audio && audio.is_none() {
+            sync = SyncMode::Timer;
+        }
+
+        let mut fps = FPSCounter::default();
+        // 70224 cycles per frame at 4.194304MHz
+        let frame_duration = Duration::from_secs_f64(70224.0 / 4_194_304.0);
+        let mut last_frame_end = Instant::now();
+        let mut link_lost = false;
+
+        while self.running {
+            if self.paused.load(Ordering::Relaxed) {
+                if let Some(audio) = &audio {
+                    audio.clear();
+                }
+                thread::sleep(Duration::from_millis(10));
+                continue;
+            }
+            let rewinding = ui.rewinding && can_rewind;
+            if rewinding {
+                if let Err(err) = rewind.rewind(&mut gameboy) {
+                    println!("{}", err);
+                }
+            } else {
+                match debugger.run_frame(&mut gameboy) {
+                    Ok(Some(stop)) => self.debug(&mut debugger, &mut gameboy, Some(stop)),
+                    Ok(None) => {}
+                    Err(err) => {
+                        println!("{}", err);
+                        break;
+                    }
+                }
+                rewind.on_frame(&gameboy);
+                if !link_lost {
+                    if let Some(err) = gameboy.link_error() {
+                        println!("{}", err);
+                        link_lost = true;
+                    }
+                }
+            }
+            let samples = gameboy.audio_samples();
+            if let Some(audio) = &mut audio {
+                if !rewinding {
+                    audio.push(&samples);
+                }
+                gameboy.set_audio_sample_rate(audio.adjusted_rate());
+            }
+            ui.update_debug_window(&gameboy.cpu.bus.ppu);
+            ui.update_memory_window(&mut gameboy.cpu.bus);
+            ui.update_cheat_window(&mut gameboy);
+            if let Some(input) = ui.update(gameboy.framebuffer()) {
+                gameboy.set_input(input);
+            }
+            let _fps = fps.tick();
+            // println!("FPS: {fps}");
+
+            if ui.exited {
+                self.running = false;
+            }
+            if ui.debug_requested {
+                ui.debug_requested = false;
+                self.debug(&mut debugger, &mut gameboy, None);
+                if let Some(audio) = &audio {
+                    audio.clear();
+                }
+            }
+
+            // the queue runs dry while rewinding, fall back to the timer then
+            match (sync, &audio, rewinding) {
+                (SyncMode::Audio, Some(audio), false) => audio.wait(),
+                (SyncMode::Vsync, _, _) => {}
+                _ => {
+                    let elapsed = last_frame_end.elapsed();
+                    if elapsed < frame_duration {
+                        thread::sleep(frame_duration - elapsed);
+                    }
+                }
+            }
+            last_frame_end = Instant::now();
+        }
+        gameboy.save_ram();
+        if let Err(err) = gameboy.stop_trace() {
+            println!("{}", err);
+        }
+        println!("Finished app");
+        Ok(())
+    }
+
+    // The window stands still while the prompt is open in the terminal
+    fn debug(&mut self, debugger: &mut Debugger, gameboy: &mut GameBoy, stop: Option<Stop>) {
+        if debugger.repl(gameboy, stop) == Resume::Quit {
+            self.running = false;
+        }
+    }
+
+    // The debugger prompt without a window or sound, `continue` runs right
+    // from the prompt
+    pub fn run_debugger(&mut self, path: &str) -> Result<(), String> {
+        let mut gameboy = self.prepare(path)?;
+        let mut debugger = Debugger::new(true);
+        debugger.repl(&mut gameboy, None);
+        gameboy.save_ram();
+        if let Err(err) = gameboy.stop_trace() {
+            println!("{}", err);
+        }
+        Ok(())
+    }
+
+    // Runs without a window or sound and prints whatever comes out of the serial
+    // port, until a test ROM reports that it passed or failed.
+    pub fn run_serial_stdout(&mut self, path: &str) -> Result<(), String> {
+        let mut gameboy = self.prepare(path)?;
+        let mut text = String::new();
+        let mut stdout = io::stdout();
+        for _ in 0..HEADLESS_FRAME_LIMIT {
+            gameboy.run_frame()?;
+            gameboy.audio_samples();
+            let output = gameboy.serial_output();
+            if output.is_empty() {
+                continue;
+            }
+            _ = stdout.write_all(&output);
+            _ = stdout.flush();
+            text.push_str(&String::from_utf8_lossy(&output));
+            if text.contains("Passed") || text.contains("Failed") {
+                break;
+            }
+        }
+        println!();
+        gameboy.save_ram();
+        if let Err(err) = gameboy.stop_trace() {
+            println!("{}", err);
+        }
+        Ok(())
+    }
+
+    // Two machines side by side, connected through their serial ports. Only
+    // the left one is heard and rewinding is off, it would pull a single side
+    // out of sync.
+    pub fn start_linked(&mut self, left_path: &str, right_path: &str) -> Result<(), String> {
+        let mut pair = LinkedPair::new(self.load(left_path)?, self.load(right_path)?);
+
+        let mut ui = Renderer::with_screens(
+            self.sync == SyncMode::Vsync,
+            vec![KeyMap::left_player(), KeyMap::right_player()],
+        );
+        let mut audio = match ui
+            .audio()
+            .and_then(|subsystem| AudioOutput::new(&subsystem, self.audio_latency))
+        {
+            Ok(audio) => {
+                pair.left.set_audio_sample_rate(audio.device_rate());
+                Some(audio)
+            }
+            Err(err) => {
+                println!("No audio: {}", err);
+                None
+            }
+        };
+
+        let frame_duration = Duration::from_secs_f64(70224.0 / 4_194_304.0);
+        let mut last_frame_end = Instant::now();
+
+        while self.running {
+            if self.paused.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(10));
+                continue;
+            }
+            if let Err(err) = pair.run_frame() {
+                println!("{}", err);
+                break;
+            }
+            let samples = pair.left.audio_samples();
+            // drained so it doesn't pile up
+            pair.right.audio_samples();
+            if let Some(audio) = &mut audio {
+                audio.push(&samples);
+                pair.left.set_audio_sample_rate(audio.adjusted_rate());
+            }
+            let inputs = ui.update_screens(&[pair.left.framebuffer(), pair.right.framebuffer()]);
+            for (gameboy, input) in [&mut pair.left, &mut pair.right].into_iter().zip(inputs) {
+                if let Some(input) = input {
+                    gameboy.set_input(input);
+                }
+            }
+            if ui.exited {
+                self.running = false;
+            }
+
+            match (self.sync, &audio) {
+                (SyncMode::Audio, Some(audio)) => audio.wait(),
+                (SyncMode::Vsync, _) => {}
+                _ => {
+                    let elapsed = last_frame_end.elapsed();
+                    if elapsed < frame_duration {
+                        thread::sleep(frame_duration - elapsed);
+                    }
+                }
+            }
+            last_frame_end = Instant::now();
+        }
+        pair.left.save_ram();
+        pair.right.save_ram();
+        println!("Finished app");
+        Ok(())
+    }
+}
@@ -0,0 +1,285 @@
+use crate::bus::Bus;
+use crate::cartridge::Cartridge;
+use crate::cheats::Cheats;
+use crate::cpu::Registers;
+use crate::cpu::{TestResult, CPU};
+use crate::input::Input;
+use crate::io::serial::{Disconnected, LinkDevice};
+use crate::model::Model;
+use crate::ppu::FrameBuffer;
+use crate::state::{self, SaveState, StateReader, StateWriter};
+use crate::symbols::Symbols;
+use crate::trace::{TraceOptions, Tracer};
+use std::rc::Rc;
+
+// Headless core. Owns the whole machine and only advances it when asked to,
+// so frontends decide about threads, pacing and presentation themselves.
+pub struct GameBoy {
+    pub cpu: CPU,
+    pub model: Model,
+    // labels for the debugger, disassembly and traces
+    pub symbols: Rc<Symbols>,
+    // change them through set_cheats or call apply_cheats afterwards
+    pub cheats: Cheats,
+}
+
+impl GameBoy {
+    // picks the model from the cartridge header
+    pub fn new(cartridge: Cartridge) -> Self {
+        let model = Model::for_cartridge(&cartridge.info);
+        GameBoy::with_model(cartridge, model)
+    }
+
+    // starts right at 0x100 with the state the model's boot ROM would have left behind
+    pub fn with_model(cartridge: Cartridge, model: Model) -> Self {
+        let registers = model.post_boot_registers(&cartridge);
+        let mut gameboy = GameBoy::build(cartridge, model);
+        gameboy.cpu.regs = registers;
+        let bus = &mut gameboy.cpu.bus;
+        bus.timer.set_div(model.post_boot_div());
+        for (address, value) in model.post_boot_io() {
+            bus.write8(address, value);
+        }
+        bus.apu.ch1.enabled = model.boot_sound();
+        gameboy
+    }
+
+    // runs a user supplied boot ROM from 0x0000 before handing over to the cartridge
+    pub fn with_boot_rom(
+        cartridge: Cartridge,
+        model: Model,
+        boot_rom: Vec<u8>,
+    ) -> Result<Self, String> {
+        if boot_rom.len() != model.boot_rom_size() {
+            return Err(format!(
+                "A {:?} boot ROM has to be {} bytes, got {}",
+                model,
+                model.boot_rom_size(),
+                boot_rom.len()
+            ));
+        }
+        let mut gameboy = GameBoy::build(cartridge, model);
+        let bus = &mut gameboy.cpu.bus;
+        bus.set_boot_rom(boot_rom);
+        bus.timer.set_div(0);
+        bus.ppu.lcd.lcdc = 0;
+        gameboy.cpu.regs = Registers::zeroed();
+        Ok(gameboy)
+    }
+
+    fn build(cartridge: Cartridge, model: Model) -> Self {
+        let mut bus = Bus::new();
+        // a CGB falls back to DMG compatibility for cartridges without CGB support
+        bus.set_cgb_mode(model.is_cgb() && cartridge.info.cgb_support());
+        bus.set_cartridge(cartridge);
+        bus.set_request_interrupt_fn();
+        GameBoy {
+            cpu: CPU::new(bus),
+            model,
+            symbols: Rc::new(Symbols::default()),
+            cheats: Cheats::default(),
+        }
+    }
+
+    // Runs the CPU until the PPU enters VBlank and a new frame is ready.
+    pub fn run_frame(&mut self) -> Result<(), String> {
+        loop {
+            self.step()?;
+            if self.cpu.bus.ppu.have_update() {
+                return Ok(());
+            }
+        }
+    }
+
+    // Executes a single CPU instruction (or one halted cycle).
+    pub fn step(&mut self) -> Result<(), String> {
+        let step_result = self.cpu.cpu_step();
+        if step_result < 0 {
+            return Err(format!("CPU Error: step_result = {}", step_result));
+        }
+        Ok(())
+    }
+
+    // makes LD B,B report Mooneye test results through test_result()
+    pub fn detect_test_result(&mut self, enabled: bool) {
+        self.cpu.detect_test_result = enabled;
+    }
+
+    pub fn test_result(&self) -> Option<TestResult> {
+        self.cpu.test_result
+    }
+
+    // logs every instruction to `path` in the Gameboy Doctor format
+    pub fn start_trace(&mut self, path: &str, options: TraceOptions) -> Result<(), String> {
+        let labels = options.labels;
+        let mut tracer = Tracer::create(path, options)?;
+        if labels {
+            tracer.symbols = Some(self.symbols.clone());
+        }
+        self.cpu.tracer = Some(tracer);
+        Ok(())
+    }
+
+    pub fn set_symbols(&mut self, symbols: Symbols) {
+        self.symbols = Rc::new(symbols);
+    }
+
+    pub fn set_cheats(&mut self, cheats: Cheats) {
+        self.cheats = cheats;
+        self.apply_cheats();
+    }
+
+    // hands the enabled cheats to the cartridge and the bus
+    pub fn apply_cheats(&mut self) {
+        self.cheats.apply(&mut self.cpu.bus);
+    }
+
+    pub fn stop_trace(&mut self) -> Result<(), String> {
+        match self.cpu.tracer.take() {
+            Some(mut tracer) => tracer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    // Gameboy Doctor expects LY to always read 0x90
+    pub fn set_ly_override(&mut self, ly: Option<u8>) {
+        self.cpu.bus.ly_override = ly;
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cpu.cycles
+    }
+
+    pub fn framebuffer(&self) -> &FrameBuffer {
+        &self.cpu.bus.ppu.video_buffer
+    }
+
+    pub fn set_input(&mut self, input: Input) {
+        self.cpu.bus.ioram.borrow_mut().input.last_input = input;
+    }
+
+    // interleaved stereo f32 samples at the configured rate, drained on every call
+    pub fn audio_samples(&mut self) -> Vec<f32> {
+        self.cpu.bus.apu.take_samples()
+    }
+
+    pub fn set_audio_sample_rate(&mut self, sample_rate: u32) {
+        self.cpu.bus.apu.set_sample_rate(sample_rate);
+    }
+
+    // plugs a peer into the serial port, replacing whatever was connected before
+    pub fn connect_link(&mut self, device: Box<dyn LinkDevice>) {
+        self.cpu.bus.ioram.borrow_mut().serial.device = device;
+    }
+
+    pub fn disconnect_link(&mut self) {
+        self.connect_link(Box::new(Disconnected));
+    }
+
+    // set once the device on the serial port has lost its connection
+    pub fn link_error(&self) -> Option<String> {
+        let ioram = self.cpu.bus.ioram.borrow();
+        ioram.serial.device.error().map(|err| err.to_string())
+    }
+
+    // bytes sent out over the serial port since the last call
+    pub fn serial_output(&mut self) -> Vec<u8> {
+        self.cpu.bus.ioram.borrow_mut().serial.take_output()
+    }
+
+    // called with every byte sent out over the serial port, as it happens
+    pub fn on_serial_output(&mut self, callback: impl FnMut(u8) + 'static) {
+        self.cpu.bus.ioram.borrow_mut().serial.on_output = Some(Box::new(callback));
+    }
+
+    pub fn cartridge(&self) -> Option<&Cartridge> {
+        self.cpu.bus.cart.as_ref()
+    }
+
+    // Versioned snapshot of the whole machine, tagged with the cartridge it was made for.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut writer = StateWriter::new();
+        if let Some(cart) = self.cartridge() {
+            state::write_header(&mut writer, &cart.info);
+        }
+        self.cpu.save_state(&mut writer);
+        writer.into_bytes()
+    }
+
+    // Leaves the machine untouched if the state is rejected or turns out to be corrupt.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), String> {
+        let mut reader = StateReader::new(data);
+        match self.cartridge() {
+            Some(cart) => state::check_header(&mut reader, &cart.info)?,
+            None => return Err("Can't load a save state without a cartridge".to_string()),
+        }
+
+        let backup = self.save_state();
+        if let Err(err) = self.cpu.load_state(&mut reader) {
+            let mut backup_reader = StateReader::new(&backup);
+            if let Some(cart) = self.cartridge() {
+                state::check_header(&mut backup_reader, &cart.info)?;
+            }
+            self.cpu.load_state(&mut backup_reader)?;
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    pub fn save_ram(&self) {
+        if let Some(cart) = self.cartridge() {
+            cart.save_ram();
+        }
+    }
+}
+
+// for the unit tests, the ROMs ship with the emulator at the repository root
+#[cfg(test)]
+impl GameBoy {
+    pub(crate) fn test_rom(name: &str) -> GameBoy {
+        let path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../../roms")
+            .join(name);
+        GameBoy::new(Cartridge::from_path(path.to_str().unwrap()).unwrap())
+    }
+
+    // returns the last frame
+    pub(crate) fn run_test_frames(&mut self, frames: u32) -> Vec<u8> {
+        for _ in 0..frames {
+            self.run_frame().unwrap();
+            self.audio_samples();
+        }
+        self.framebuffer().to_rgba8888()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_the_same_after_loading() {
+        let mut gameboy = GameBoy::test_rom("Tetris.gb");
+        gameboy.run_test_frames(100);
+        let state = gameboy.save_state();
+        let first = gameboy.run_test_frames(90);
+        let first_state = gameboy.save_state();
+
+        gameboy.load_state(&state).unwrap();
+        let second = gameboy.run_test_frames(90);
+        assert!(first == second, "the frames differ");
+        assert_eq!(gameboy.save_state(), first_state);
+    }
+
+    #[test]
+    fn rejects_another_games_state() {
+        let mut gameboy = GameBoy::test_rom("Tetris.gb");
+        gameboy.run_test_frames(10);
+        let mut other = GameBoy::test_rom("dmg-acid2.gb");
+        other.run_test_frames(10);
+
+        let before = gameboy.save_state();
+        assert!(gameboy.load_state(&other.save_state()).is_err());
+        assert_eq!(gameboy.save_state(), before);
+    }
+}
@@ -1098,7 +1098,7 @@ impl Instruction {
                 ..Default::default()
             },
         };
-        // inst.length = Instruction::length(&inst);
+        inst.length = Instruction::length(&inst);
         inst.opcode = *code;
         return inst;
     }
@@ -1141,29 +1141,25 @@ impl Instruction {
             // self.no_action_cycles.to_string()
         );
     }
-    /* pub fn length(inst: &Instruction) -> u8 {
-         match inst.address_mode {
-             AddressMode::IMPLIED => 1,
-             AddressMode::R | AddressMode::MR => 1,
-             AddressMode::R_R | AddressMode::R_MR | AddressMode::MR_R => 1,
-             AddressMode::R_D8
-             | AddressMode::MR_D8
-             | AddressMode::A8_R
-             | AddressMode::R_A8
-             | AddressMode::HL_SPR
-             | AddressMode::D8
-             | AddressMode::R_HLI
-             | AddressMode::R_HLD
-             | AddressMode::HLI_R
-             | AddressMode::HLD_R => 2,
-             AddressMode::R_D16
-             | AddressMode::R_A16
-             | AddressMode::D16_R
-             | AddressMode::A16_R
-             | AddressMode::D16 => 3,
-         }
-     }
-    */
+    pub fn length(inst: &Instruction) -> u8 {
+        match inst.address_mode {
+            AddressMode::IMPLIED => 1,
+            AddressMode::R | AddressMode::MR => 1,
+            AddressMode::R_R | AddressMode::R_MR | AddressMode::MR_R => 1,
+            AddressMode::R_HLI | AddressMode::R_HLD | AddressMode::HLI_R | AddressMode::HLD_R => 1,
+            AddressMode::R_D8
+            | AddressMode::MR_D8
+            | AddressMode::A8_R
+            | AddressMode::R_A8
+            | AddressMode::HL_SPR
+            | AddressMode::D8 => 2,
+            AddressMode::R_D16
+            | AddressMode::R_A16
+            | AddressMode::D16_R
+            | AddressMode::A16_R
+            | AddressMode::D16 => 3,
+        }
+    }
     // pub fn get_cycles_count(inst: &Instruction) -> (u8, Option<u8>) {
     //     match (&inst.instruction_type, &inst.address_mode, &inst.condition) {
     //         // Conditional instructions
@@ -1,15 +1,20 @@
 #[path = "lcd.rs"]
 pub mod lcd;
+#[path = "serial.rs"]
+pub mod serial;
 
 use std::sync::Arc;
 
+use self::serial::Serial;
 use super::input::{Input, InputManager};
+use super::interrupts::InterruptType;
+use super::state::{SaveState, StateReader, StateWriter};
 
 pub struct IOManager {
     // pub ram: [u8; 0x80],
     pub input: InputManager,
     pub input_requested: bool,
-    pub serial_data: [u8; 2],
+    pub serial: Serial,
     pub interrupt_flags: u8,
 }
 
@@ -19,7 +24,7 @@ impl IOManager {
             // ram: [0; 0x80],
             input: InputManager::new(),
             input_requested: false,
-            serial_data: [0; 2],
+            serial: Serial::new(),
             interrupt_flags: 0,
         }
     }
@@ -29,8 +34,7 @@ impl IOManager {
             0xFF00 => {
                 self.input.set_mode(value);
             }
-            0xFF01 => self.serial_data[0] = value,
-            0xFF02 => self.serial_data[1] = value,
+            0xFF01..=0xFF02 => self.serial.write(address, value),
             0xFF0F => self.interrupt_flags = value,
             _ => {}
         }
@@ -38,11 +42,34 @@ impl IOManager {
     pub fn read(&self, address: usize) -> u8 {
         match address {
             0xFF00 => self.input.gamepad_get_output(),
-            0xFF01 => self.serial_data[0],
-            0xFF02 => self.serial_data[1],
+            0xFF01..=0xFF02 => self.serial.read(address),
             0xFF0F => self.interrupt_flags,
             _ => 0,
         }
     }
+    pub fn serial_tick(&mut self, cycles: u32, elapsed: u32) {
+        if self.serial.tick(cycles, elapsed) {
+            self.interrupt_flags |= InterruptType::SERIAL;
+        }
+    }
     pub fn update_input(&self, input: Arc<Input>) {}
 }
+
+impl SaveState for IOManager {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bool(self.input.d_pad_mode);
+        writer.write_bool(self.input.button_mode);
+        writer.write_bool(self.input_requested);
+        self.serial.save_state(writer);
+        writer.write_u8(self.interrupt_flags);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.input.d_pad_mode = reader.read_bool()?;
+        self.input.button_mode = reader.read_bool()?;
+        self.input_requested = reader.read_bool()?;
+        self.serial.load_state(reader)?;
+        self.interrupt_flags = reader.read_u8()?;
+        Ok(())
+    }
+}
@@ -1,23 +1,23 @@
 use std::ops::{BitAnd, BitOr};
 
-use sdl2::pixels::Color;
-
 use crate::dma::DMA;
+use crate::ppu::Color;
+use crate::state::{SaveState, StateReader, StateWriter};
 
 pub static COLORS: [Color; 4] = [
     //E1F8CF
-    Color::RGB(0xE1, 0xF8, 0xCF),
+    Color::rgb(0xE1, 0xF8, 0xCF),
     //87C06C
-    Color::RGB(0x87, 0xC0, 0x6C),
+    Color::rgb(0x87, 0xC0, 0x6C),
     //2F6850
-    Color::RGB(0x2F, 0x68, 0x50),
+    Color::rgb(0x2F, 0x68, 0x50),
     //071821
-    Color::RGB(0x07, 0x18, 0x21),
+    Color::rgb(0x07, 0x18, 0x21),
 ];
 // Color::WHITE,
-// Color::RGB(0xAA, 0xAA, 0xAA),
-// Color::RGB(0x55, 0x55, 0x55),
-// Color::RGB(0x00, 0x00, 0x00),
+// Color::rgb(0xAA, 0xAA, 0xAA),
+// Color::rgb(0x55, 0x55, 0x55),
+// Color::rgb(0x00, 0x00, 0x00),
 
 pub enum StatType {
     HBLANK = (1 << 3),
@@ -65,6 +65,12 @@ pub struct LCD {
     pub obj0_colors: [Color; 4],
     pub obj1_colors: [Color; 4],
     pub dma: DMA, //ff46
+
+    // CGB palette RAM, 8 palettes of 4 little endian RGB555 colors each
+    pub bg_palette_spec: u8,       //ff68
+    pub bg_palette_ram: [u8; 64],  //ff69
+    pub obj_palette_spec: u8,      //ff6a
+    pub obj_palette_ram: [u8; 64], //ff6b
 }
 
 impl LCD {
@@ -85,12 +91,20 @@ impl LCD {
             bg_colors: COLORS.clone(),
             obj0_colors: COLORS.clone(),
             obj1_colors: COLORS.clone(),
+            bg_palette_spec: 0,
+            bg_palette_ram: [0xFF; 64],
+            obj_palette_spec: 0,
+            obj_palette_ram: [0xFF; 64],
         };
     }
     // 0: BG & Window enable / priority [Different meaning in CGB Mode]: 0 = Off; 1 = On
     pub fn lcdc_bgw_enabled(&self) -> bool {
         return (self.lcdc & 1) == 0;
     }
+    // in CGB Mode bit 0 doesn't hide the BG, clearing it puts all objects above it instead
+    pub fn lcdc_bg_priority(&self) -> bool {
+        return (self.lcdc & 1) > 0;
+    }
     // 1: OBJ enable: 0 = Off; 1 = On
     pub fn lcdc_obj_enabled(&self) -> bool {
         return (self.lcdc >> 1) & 1 > 0;
@@ -191,6 +205,10 @@ impl LCD {
             0xff49 => self.obj_pallete[1],
             0xff4a => self.win_y,
             0xff4b => self.win_x,
+            0xff68 => self.bg_palette_spec | 0x40,
+            0xff69 => self.bg_palette_ram[(self.bg_palette_spec & 0x3F) as usize],
+            0xff6a => self.obj_palette_spec | 0x40,
+            0xff6b => self.obj_palette_ram[(self.obj_palette_spec & 0x3F) as usize],
             _ => 0,
         }
     }
@@ -221,9 +239,36 @@ impl LCD {
             }
             0xff4a => self.win_y = value,
             0xff4b => self.win_x = value,
+            0xff68 => self.bg_palette_spec = value & 0xBF,
+            0xff69 => {
+                self.bg_palette_ram[(self.bg_palette_spec & 0x3F) as usize] = value;
+                self.bg_palette_spec = palette_spec_increment(self.bg_palette_spec);
+            }
+            0xff6a => self.obj_palette_spec = value & 0xBF,
+            0xff6b => {
+                self.obj_palette_ram[(self.obj_palette_spec & 0x3F) as usize] = value;
+                self.obj_palette_spec = palette_spec_increment(self.obj_palette_spec);
+            }
             _ => {}
         }
     }
+
+    // Like write, but DMA doesn't start and the palette indexes stay put
+    pub fn poke(&mut self, address: usize, value: u8) {
+        match address {
+            0xff46 => self.dma_address = value,
+            0xff69 => self.bg_palette_ram[(self.bg_palette_spec & 0x3F) as usize] = value,
+            0xff6b => self.obj_palette_ram[(self.obj_palette_spec & 0x3F) as usize] = value,
+            _ => self.write(address, value),
+        }
+    }
+
+    pub fn cgb_bg_color(&self, palette: u8, index: usize) -> Color {
+        cgb_color(&self.bg_palette_ram, palette, index)
+    }
+    pub fn cgb_obj_color(&self, palette: u8, index: usize) -> Color {
+        cgb_color(&self.obj_palette_ram, palette, index)
+    }
     pub fn update_palette(&mut self, data: u8, palette: u8) {
         // let colors = match palette {
         //     1 => &mut self.obj0_colors,
@@ -256,6 +301,26 @@ impl LCD {
     }
 }
 
+// bit 7 of BCPS/OCPS advances the address after every data write
+fn palette_spec_increment(spec: u8) -> u8 {
+    if spec & 0x80 > 0 {
+        0x80 | (spec.wrapping_add(1) & 0x3F)
+    } else {
+        spec
+    }
+}
+
+fn cgb_color(palette_ram: &[u8; 64], palette: u8, index: usize) -> Color {
+    let offset = (palette as usize & 0x07) * 8 + index * 2;
+    let value = palette_ram[offset] as u16 | ((palette_ram[offset + 1] as u16) << 8);
+    // 5 bit channels stretched to 8 bits
+    let channel = |shift: u16| {
+        let c = ((value >> shift) & 0x1F) as u8;
+        (c << 3) | (c >> 2)
+    };
+    Color::rgb(channel(0), channel(5), channel(10))
+}
+
 #[repr(u8)]
 #[derive(Debug)]
 pub enum Mode {
@@ -285,3 +350,47 @@ impl BitOr<Mode> for u8 {
 //         *self |= rhs as u8;
 //     }
 // }
+
+impl SaveState for LCD {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.lcdc);
+        writer.write_u8(self.lcds);
+        writer.write_u8(self.scroll_y);
+        writer.write_u8(self.scroll_x);
+        writer.write_u8(self.ly);
+        writer.write_u8(self.ly_compare);
+        writer.write_u8(self.dma_address);
+        writer.write_u8(self.bg_pallete);
+        writer.write_bytes(&self.obj_pallete);
+        writer.write_u8(self.win_y);
+        writer.write_u8(self.win_x);
+        writer.write_u8(self.bg_palette_spec);
+        writer.write_bytes(&self.bg_palette_ram);
+        writer.write_u8(self.obj_palette_spec);
+        writer.write_bytes(&self.obj_palette_ram);
+        self.dma.save_state(writer);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.lcdc = reader.read_u8()?;
+        self.lcds = reader.read_u8()?;
+        self.scroll_y = reader.read_u8()?;
+        self.scroll_x = reader.read_u8()?;
+        self.ly = reader.read_u8()?;
+        self.ly_compare = reader.read_u8()?;
+        self.dma_address = reader.read_u8()?;
+        self.bg_pallete = reader.read_u8()?;
+        reader.read_bytes(&mut self.obj_pallete)?;
+        self.win_y = reader.read_u8()?;
+        self.win_x = reader.read_u8()?;
+        self.bg_palette_spec = reader.read_u8()?;
+        reader.read_bytes(&mut self.bg_palette_ram)?;
+        self.obj_palette_spec = reader.read_u8()?;
+        reader.read_bytes(&mut self.obj_palette_ram)?;
+        // the color tables are derived from the palette registers
+        self.update_palette(self.bg_pallete, 0);
+        self.update_palette(self.obj_pallete[0] & !0b11, 1);
+        self.update_palette(self.obj_pallete[1] & !0b11, 2);
+        self.dma.load_state(reader)
+    }
+}
@@ -0,0 +1,159 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::io::serial::LinkDevice;
+use crate::GameBoy;
+
+// What travels between the two ends of the cable, indexed by side
+#[derive(Default)]
+struct Wire {
+    // SB of a side that waits for the other one to drive the clock
+    waiting: [Option<u8>; 2],
+    // bytes the other side clocked in, handed over on the next poll
+    incoming: [Option<u8>; 2],
+}
+
+// One plug of an in-process link cable
+pub struct LinkPort {
+    wire: Rc<RefCell<Wire>>,
+    side: usize,
+}
+
+// Both ends of a cable, plug one into each machine
+pub fn link_cable() -> (LinkPort, LinkPort) {
+    let wire = Rc::new(RefCell::new(Wire::default()));
+    (
+        LinkPort {
+            wire: Rc::clone(&wire),
+            side: 0,
+        },
+        LinkPort { wire, side: 1 },
+    )
+}
+
+impl LinkDevice for LinkPort {
+    fn exchange(&mut self, data: u8) -> u8 {
+        let mut wire = self.wire.borrow_mut();
+        // driving the clock ourselves, anything left over from waiting is stale
+        wire.waiting[self.side] = None;
+        wire.incoming[self.side] = None;
+        let peer = 1 - self.side;
+        match wire.waiting[peer].take() {
+            Some(reply) => {
+                wire.incoming[peer] = Some(data);
+                reply
+            }
+            // the other side isn't listening, the line stays high
+            None => 0xFF,
+        }
+    }
+
+    fn clock_in(&mut self, data: u8) -> Option<u8> {
+        let mut wire = self.wire.borrow_mut();
+        match wire.incoming[self.side].take() {
+            Some(incoming) => {
+                wire.waiting[self.side] = None;
+                Some(incoming)
+            }
+            None => {
+                wire.waiting[self.side] = Some(data);
+                None
+            }
+        }
+    }
+}
+
+// Two machines connected by a link cable. They are stepped in lockstep on the
+// cycle counter and never drift apart by more than an instruction, so every
+// transfer plays out the same way on every run.
+pub struct LinkedPair {
+    pub left: GameBoy,
+    pub right: GameBoy,
+}
+
+impl LinkedPair {
+    pub fn new(mut left: GameBoy, mut right: GameBoy) -> Self {
+        let (left_port, right_port) = link_cable();
+        left.connect_link(Box::new(left_port));
+        right.connect_link(Box::new(right_port));
+        LinkedPair { left, right }
+    }
+
+    // Runs both machines until the left one has a new frame ready.
+    pub fn run_frame(&mut self) -> Result<(), String> {
+        loop {
+            if self.left.cycles() <= self.right.cycles() {
+                self.left.step()?;
+                if self.left.cpu.bus.ppu.have_update() {
+                    return Ok(());
+                }
+            } else {
+                self.right.step()?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::serial::Serial;
+
+    fn plugged(port: LinkPort, sb: u8, sc: u8) -> Serial {
+        let mut serial = Serial::new();
+        serial.device = Box::new(port);
+        serial.write(0xFF01, sb);
+        serial.write(0xFF02, sc);
+        serial
+    }
+
+    #[test]
+    fn cable_exchange() {
+        let (master_port, slave_port) = link_cable();
+        let mut slave = plugged(slave_port, 0x22, 0x80);
+        let mut master = plugged(master_port, 0x11, 0x81);
+
+        let (mut master_interrupts, mut slave_interrupts) = (0, 0);
+        // 8 bits at 512 T-cycles and some time for the slave to see the last one
+        for _ in 0..1100 {
+            slave_interrupts += slave.tick(4, 4) as u32;
+            master_interrupts += master.tick(4, 4) as u32;
+        }
+        assert_eq!((master_interrupts, slave_interrupts), (1, 1));
+        assert_eq!(master.sb, 0x22);
+        assert_eq!(slave.sb, 0x11);
+        assert_eq!(master.sc & 0x80, 0);
+        assert_eq!(slave.sc & 0x80, 0);
+        assert_eq!(master.take_output(), [0x11]);
+        assert_eq!(slave.take_output(), [0x22]);
+    }
+
+    #[test]
+    fn nobody_listening() {
+        let (master_port, _slave_port) = link_cable();
+        let mut master = plugged(master_port, 0x11, 0x81);
+        let interrupts: u32 = (0..1100).map(|_| master.tick(4, 4) as u32).sum();
+        assert_eq!(interrupts, 1);
+        assert_eq!(master.sb, 0xFF);
+    }
+
+    fn linked_run(frames: u32) -> (Vec<u8>, Vec<u8>) {
+        let mut pair = LinkedPair::new(
+            GameBoy::test_rom("Tetris.gb"),
+            GameBoy::test_rom("Tetris.gb"),
+        );
+        for _ in 0..frames {
+            pair.run_frame().unwrap();
+            pair.left.audio_samples();
+            pair.right.audio_samples();
+        }
+        (pair.left.save_state(), pair.right.save_state())
+    }
+
+    #[test]
+    fn linked_pair_is_deterministic() {
+        let first = linked_run(200);
+        let second = linked_run(200);
+        assert!(first.0 == second.0, "the left machines differ");
+        assert!(first.1 == second.1, "the right machines differ");
+    }
+}
@@ -0,0 +1,199 @@
+use crate::bus::Bus;
+
+// Raw memory for debuggers, scripts and cheats. Every domain is addressed
+// by bank and offset into that bank, whatever the MBC has mapped and
+// whatever the PPU is doing, and nothing a read or write would trigger on
+// the bus happens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryDomain {
+    Rom,
+    Sram,
+    Wram,
+    Vram,
+    Oam,
+    Hram,
+    Io,
+}
+
+pub static DOMAINS: [MemoryDomain; 7] = [
+    MemoryDomain::Rom,
+    MemoryDomain::Sram,
+    MemoryDomain::Wram,
+    MemoryDomain::Vram,
+    MemoryDomain::Oam,
+    MemoryDomain::Hram,
+    MemoryDomain::Io,
+];
+
+impl MemoryDomain {
+    pub fn from_name(name: &str) -> Option<MemoryDomain> {
+        match name.to_ascii_lowercase().as_str() {
+            "rom" => Some(MemoryDomain::Rom),
+            "sram" => Some(MemoryDomain::Sram),
+            "wram" => Some(MemoryDomain::Wram),
+            "vram" => Some(MemoryDomain::Vram),
+            "oam" => Some(MemoryDomain::Oam),
+            "hram" => Some(MemoryDomain::Hram),
+            "io" => Some(MemoryDomain::Io),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MemoryDomain::Rom => "ROM",
+            MemoryDomain::Sram => "SRAM",
+            MemoryDomain::Wram => "WRAM",
+            MemoryDomain::Vram => "VRAM",
+            MemoryDomain::Oam => "OAM",
+            MemoryDomain::Hram => "HRAM",
+            MemoryDomain::Io => "IO",
+        }
+    }
+
+    // bytes in one bank, the way the bank shows up on the bus
+    pub fn bank_size(&self) -> usize {
+        match self {
+            MemoryDomain::Rom => 0x4000,
+            MemoryDomain::Sram => 0x2000,
+            MemoryDomain::Wram => 0x1000,
+            MemoryDomain::Vram => 0x2000,
+            MemoryDomain::Oam => 0xA0,
+            MemoryDomain::Hram => 0x7F,
+            MemoryDomain::Io => 0x80,
+        }
+    }
+}
+
+impl Bus {
+    // banks the hardware has, 0 for SRAM on cartridges without RAM
+    pub fn domain_banks(&self, domain: MemoryDomain) -> usize {
+        match domain {
+            MemoryDomain::Rom => self
+                .cart
+                .as_ref()
+                .map_or(0, |cart| cart.info.rom_bank_count),
+            MemoryDomain::Sram => self
+                .cart
+                .as_ref()
+                .map_or(0, |cart| cart.info.ram_bank_count as usize),
+            MemoryDomain::Wram if self.cgb_mode => 8,
+            MemoryDomain::Wram => 2,
+            MemoryDomain::Vram if self.cgb_mode => 2,
+            _ => 1,
+        }
+    }
+
+    fn check_domain(&self, domain: MemoryDomain, bank: usize, offset: usize) -> Result<(), String> {
+        if bank >= self.domain_banks(domain) {
+            return Err(format!("{} has no bank {:X}", domain.name(), bank));
+        }
+        if offset >= domain.bank_size() {
+            return Err(format!(
+                "{:X} is past the end of a {} bank",
+                offset,
+                domain.name()
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn domain_read(
+        &self,
+        domain: MemoryDomain,
+        bank: usize,
+        offset: usize,
+    ) -> Result<u8, String> {
+        self.check_domain(domain, bank, offset)?;
+        let cart = self.cart.as_ref();
+        Ok(match domain {
+            MemoryDomain::Rom => cart.map_or(0xFF, |cart| cart.read_rom(bank, offset)),
+            MemoryDomain::Sram => cart.map_or(0xFF, |cart| cart.read_ram(bank, offset)),
+            MemoryDomain::Wram => self.wram[bank * 0x1000 + offset],
+            MemoryDomain::Vram => self.ppu.vram_read_bank(bank, 0x8000 + offset),
+            MemoryDomain::Oam => self.ppu.oam_read(offset),
+            MemoryDomain::Hram => self.hram[offset],
+            // the real LY, not what --doctor makes the CPU see
+            MemoryDomain::Io if offset == 0x44 => self.ppu.lcd.ly,
+            MemoryDomain::Io => self.read8_unwatched(0xFF00 + offset),
+        })
+    }
+
+    // ROM is patched in memory. IO registers only store the value: FF46
+    // doesn't start a DMA, FF04 sets DIV instead of resetting it, FF02
+    // doesn't start a transfer and so on.
+    pub fn domain_write(
+        &mut self,
+        domain: MemoryDomain,
+        bank: usize,
+        offset: usize,
+        value: u8,
+    ) -> Result<(), String> {
+        self.check_domain(domain, bank, offset)?;
+        match domain {
+            MemoryDomain::Rom => self
+                .cart
+                .as_mut()
+                .ok_or("No cartridge")?
+                .write_rom(bank, offset, value),
+            MemoryDomain::Sram => self
+                .cart
+                .as_mut()
+                .ok_or("No cartridge")?
+                .write_ram(bank, offset, value),
+            MemoryDomain::Wram => self.wram[bank * 0x1000 + offset] = value,
+            MemoryDomain::Vram => self.ppu.vram[bank * 0x2000 + offset] = value,
+            MemoryDomain::Oam => self.ppu.oam_write(offset, value),
+            MemoryDomain::Hram => self.hram[offset] = value,
+            MemoryDomain::Io => self.io_write_raw(0xFF00 + offset, value),
+        }
+        Ok(())
+    }
+
+    fn io_write_raw(&mut self, address: usize, value: u8) {
+        match address {
+            0xFF00 => self.ioram.borrow_mut().input.set_mode(value),
+            0xFF01..=0xFF02 => self.ioram.borrow_mut().serial.poke(address, value),
+            0xFF04 => self.timer.set_div((value as u16) << 8),
+            0xFF05..=0xFF07 => self.timer.write_byte(address, value),
+            0xFF0F => self.ioram.borrow_mut().interrupt_flags = value,
+            0xFF10..=0xFF3F => self.apu.poke(address, value),
+            0xFF40..=0xFF4B => self.ppu.lcd.poke(address, value),
+            0xFF4D if self.cgb_mode => self.speed_switch_armed = value & 1 > 0,
+            0xFF4F => self.ppu.vbk_write(value),
+            0xFF51..=0xFF55 if self.cgb_mode => self.hdma.poke(address, value),
+            0xFF68..=0xFF6B if self.cgb_mode => self.ppu.lcd.poke(address, value),
+            0xFF70 if self.cgb_mode => self.wram_bank = ((value & 0x07) as usize).max(1),
+            _ => {}
+        }
+    }
+
+    // The domain, bank and offset behind a bus address, with `rom_bank` and
+    // `sram_bank` in the switchable slots. Echo RAM is WRAM, the unused
+    // area and IE have no domain.
+    pub fn locate(
+        &self,
+        address: u16,
+        rom_bank: usize,
+        sram_bank: usize,
+    ) -> Option<(MemoryDomain, usize, usize)> {
+        let address = address as usize;
+        Some(match address {
+            0..0x4000 => (MemoryDomain::Rom, 0, address),
+            0x4000..0x8000 => (MemoryDomain::Rom, rom_bank, address - 0x4000),
+            0x8000..0xA000 => (MemoryDomain::Vram, self.ppu.vram_bank, address - 0x8000),
+            0xA000..0xC000 => (MemoryDomain::Sram, sram_bank, address - 0xA000),
+            0xC000..0xFE00 => {
+                let offset = (address - 0xC000) % 0x2000;
+                match offset {
+                    0..0x1000 => (MemoryDomain::Wram, 0, offset),
+                    _ => (MemoryDomain::Wram, self.wram_bank, offset - 0x1000),
+                }
+            }
+            0xFE00..0xFEA0 => (MemoryDomain::Oam, 0, address - 0xFE00),
+            0xFF00..0xFF80 => (MemoryDomain::Io, 0, address - 0xFF00),
+            0xFF80..0xFFFF => (MemoryDomain::Hram, 0, address - 0xFF80),
+            _ => return None,
+        })
+    }
+}
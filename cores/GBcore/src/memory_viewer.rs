@@ -0,0 +1,318 @@
+use sdl2::keyboard::Keycode;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+use crate::bus::Bus;
+use crate::ppu::Color;
+use crate::rendering::{DebugImage, LABEL_COLOR, TEXT_COLOR};
+
+static ROWS: usize = 32;
+static ROW_BYTES: usize = 16;
+static PAGE: usize = ROWS * ROW_BYTES;
+static WIDTH: usize = 584;
+static HEIGHT: usize = 452;
+static TOP_Y: usize = 36;
+static HEX_X: usize = 56;
+static TEXT_X: usize = HEX_X + ROW_BYTES * 24 + 8;
+// a single frame is too short to see, changes stay lit for a while
+static HIGHLIGHT_FRAMES: u8 = 30;
+static CHANGED_COLOR: Color = Color::rgb(0xFF, 0x60, 0x40);
+static CURSOR_COLOR: Color = Color::rgb(0x30, 0x50, 0xA0);
+
+// where each region starts, Tab jumps between them
+static REGIONS: [(&str, u16); 12] = [
+    ("ROM0", 0x0000),
+    ("ROMX", 0x4000),
+    ("VRAM", 0x8000),
+    ("SRAM", 0xA000),
+    ("WRAM0", 0xC000),
+    ("WRAMX", 0xD000),
+    ("ECHO", 0xE000),
+    ("OAM", 0xFE00),
+    ("UNUSED", 0xFEA0),
+    ("IO", 0xFF00),
+    ("HRAM", 0xFF80),
+    ("IE", 0xFFFF),
+];
+
+fn region(address: u16) -> usize {
+    REGIONS
+        .iter()
+        .rposition(|(_, start)| *start <= address)
+        .unwrap_or(0)
+}
+
+// Hex editor over the whole address space, F10 opens and closes it. ROM
+// and cartridge RAM banks can be picked by hand, edits go through
+// Bus::poke so they don't switch banks or start anything.
+pub struct MemoryViewer {
+    canvas: Canvas<Window>,
+    // first address on screen
+    top: u16,
+    cursor: u16,
+    // None follows whatever the MBC has mapped
+    rom_bank: Option<usize>,
+    sram_bank: Option<usize>,
+    // mapped banks and bank counts as of the last frame
+    mapped: (usize, usize),
+    bank_counts: (usize, usize),
+    // the high nibble of the byte being typed
+    nibble: Option<u8>,
+    // typed since the last frame, written on the next one
+    edits: Vec<(u16, u8, usize, usize)>,
+    // what was on screen last frame, and where from
+    previous: Option<(u16, usize, usize, Vec<u8>)>,
+    // frames left to highlight every byte on screen
+    highlight: Vec<u8>,
+    message: String,
+}
+
+impl MemoryViewer {
+    pub fn open(sdl_context: &sdl2::Sdl) -> Result<Self, String> {
+        let window = sdl_context
+            .video()?
+            .window("Rustboy memory", WIDTH as u32, HEIGHT as u32)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+        Ok(MemoryViewer {
+            canvas,
+            top: 0xC000,
+            cursor: 0xC000,
+            rom_bank: None,
+            sram_bank: None,
+            mapped: (1, 0),
+            bank_counts: (2, 0),
+            nibble: None,
+            edits: Vec::new(),
+            previous: None,
+            highlight: vec![0; PAGE],
+            message: String::new(),
+        })
+    }
+
+    pub fn window_id(&self) -> u32 {
+        self.canvas.window().id()
+    }
+
+    fn banks(&self) -> (usize, usize) {
+        (
+            self.rom_bank.unwrap_or(self.mapped.0),
+            self.sram_bank.unwrap_or(self.mapped.1),
+        )
+    }
+
+    fn move_cursor(&mut self, offset: i32) {
+        self.cursor = self.cursor.wrapping_add(offset as u16);
+        self.nibble = None;
+        // keep it on screen, scrolling a row at a time
+        let row = self.cursor & !(ROW_BYTES as u16 - 1);
+        if self.cursor.wrapping_sub(self.top) as usize >= PAGE {
+            self.top = if offset < 0 {
+                row
+            } else {
+                row.wrapping_sub(((ROWS - 1) * ROW_BYTES) as u16)
+            };
+        }
+    }
+
+    fn jump(&mut self, address: u16) {
+        self.top = address & !(ROW_BYTES as u16 - 1);
+        self.cursor = address;
+        self.nibble = None;
+    }
+
+    // picks the next bank in `direction`, starting from the mapped one
+    fn next_bank(
+        selected: Option<usize>,
+        mapped: usize,
+        count: usize,
+        direction: i32,
+    ) -> Option<usize> {
+        if count == 0 {
+            return None;
+        }
+        let bank = selected.unwrap_or(mapped) as i32 + direction;
+        Some(bank.rem_euclid(count as i32) as usize)
+    }
+
+    pub fn key(&mut self, key: Keycode, shift: bool) {
+        self.message.clear();
+        match key {
+            Keycode::UP => self.move_cursor(-(ROW_BYTES as i32)),
+            Keycode::DOWN => self.move_cursor(ROW_BYTES as i32),
+            Keycode::LEFT => self.move_cursor(-1),
+            Keycode::RIGHT => self.move_cursor(1),
+            Keycode::PAGEUP => {
+                self.top = self.top.wrapping_sub(PAGE as u16);
+                self.cursor = self.cursor.wrapping_sub(PAGE as u16);
+                self.nibble = None;
+            }
+            Keycode::PAGEDOWN => {
+                self.top = self.top.wrapping_add(PAGE as u16);
+                self.cursor = self.cursor.wrapping_add(PAGE as u16);
+                self.nibble = None;
+            }
+            Keycode::TAB => {
+                let current = region(self.cursor);
+                let next = if shift {
+                    (current + REGIONS.len() - 1) % REGIONS.len()
+                } else {
+                    (current + 1) % REGIONS.len()
+                };
+                self.jump(REGIONS[next].1);
+            }
+            Keycode::LEFTBRACKET | Keycode::RIGHTBRACKET => {
+                let direction = if key == Keycode::LEFTBRACKET { -1 } else { 1 };
+                self.rom_bank = MemoryViewer::next_bank(
+                    self.rom_bank,
+                    self.mapped.0,
+                    self.bank_counts.0,
+                    direction,
+                );
+            }
+            Keycode::MINUS | Keycode::EQUALS => {
+                let direction = if key == Keycode::MINUS { -1 } else { 1 };
+                self.sram_bank = MemoryViewer::next_bank(
+                    self.sram_bank,
+                    self.mapped.1,
+                    self.bank_counts.1,
+                    direction,
+                );
+            }
+            Keycode::M => {
+                self.rom_bank = None;
+                self.sram_bank = None;
+            }
+            Keycode::ESCAPE => self.nibble = None,
+            _ => {
+                let name = key.name();
+                let digit = match name.chars().next() {
+                    Some(c) if name.len() == 1 => c.to_digit(16),
+                    _ => None,
+                };
+                let Some(digit) = digit else {
+                    return;
+                };
+                match self.nibble.take() {
+                    None => self.nibble = Some(digit as u8),
+                    Some(high) => {
+                        let (rom_bank, sram_bank) = self.banks();
+                        self.edits.push((
+                            self.cursor,
+                            high << 4 | digit as u8,
+                            rom_bank,
+                            sram_bank,
+                        ));
+                        self.move_cursor(1);
+                    }
+                }
+            }
+        }
+    }
+
+    // Writes what was typed, then draws the memory as it is now
+    pub fn update(&mut self, bus: &mut Bus) {
+        for (address, value, rom_bank, sram_bank) in self.edits.drain(..) {
+            if let Err(err) = bus.poke(address, value, rom_bank, sram_bank) {
+                self.message = err;
+            }
+        }
+        if let Some(cart) = &bus.cart {
+            self.mapped = (cart.rom_bank(), cart.ram_bank());
+            self.bank_counts = (cart.info.rom_bank_count, cart.info.ram_bank_count as usize);
+        }
+        let (rom_bank, sram_bank) = self.banks();
+        let bytes: Vec<u8> = (0..PAGE)
+            .map(|offset| bus.peek(self.top.wrapping_add(offset as u16), rom_bank, sram_bank))
+            .collect();
+
+        // a different page or bank isn't a change
+        match &self.previous {
+            Some((top, rom, sram, previous))
+                if (*top, *rom, *sram) == (self.top, rom_bank, sram_bank) =>
+            {
+                for ((frames, old), new) in self.highlight.iter_mut().zip(previous).zip(&bytes) {
+                    *frames = if old != new {
+                        HIGHLIGHT_FRAMES
+                    } else {
+                        frames.saturating_sub(1)
+                    };
+                }
+            }
+            _ => self.highlight.fill(0),
+        }
+
+        let image = self.draw(&bytes, rom_bank, sram_bank);
+        self.previous = Some((self.top, rom_bank, sram_bank, bytes));
+        if let Err(err) = image.present(&mut self.canvas) {
+            println!("Memory viewer: {}", err);
+        }
+    }
+
+    fn draw(&self, bytes: &[u8], rom_bank: usize, sram_bank: usize) -> DebugImage {
+        let mut image = DebugImage::new(WIDTH, HEIGHT);
+        let following = |selected: Option<usize>| if selected.is_none() { " MAPPED" } else { "" };
+        let sram = match self.bank_counts.1 {
+            0 => "NO SRAM".to_string(),
+            count => format!(
+                "SRAM BANK {:02X}/{:02X}{}",
+                sram_bank,
+                count - 1,
+                following(self.sram_bank)
+            ),
+        };
+        let banks = format!(
+            "ROM BANK {:02X}/{:02X}{}   {}",
+            rom_bank,
+            self.bank_counts.0.saturating_sub(1),
+            following(self.rom_bank),
+            sram
+        );
+        image.text(8, 4, &banks, LABEL_COLOR);
+        let status = format!(
+            "{} {:04X}  {}",
+            REGIONS[region(self.cursor)].0,
+            self.cursor,
+            self.message
+        );
+        image.text(8, 18, &status, TEXT_COLOR);
+
+        for row in 0..ROWS {
+            let address = self.top.wrapping_add((row * ROW_BYTES) as u16);
+            let y = TOP_Y + row * 12;
+            image.text(8, y, &format!("{:04X}", address), LABEL_COLOR);
+            for column in 0..ROW_BYTES {
+                let index = row * ROW_BYTES + column;
+                let byte_address = address.wrapping_add(column as u16);
+                let x = HEX_X + column * 24;
+                let color = if self.highlight[index] > 0 {
+                    CHANGED_COLOR
+                } else {
+                    TEXT_COLOR
+                };
+                let text = match self.nibble {
+                    Some(high) if byte_address == self.cursor => format!("{:X}-", high),
+                    _ => format!("{:02X}", bytes[index]),
+                };
+                if byte_address == self.cursor {
+                    image.fill(x - 2, y - 2, 20, 14, CURSOR_COLOR);
+                    image.fill(TEXT_X + column * 8 - 1, y - 2, 8, 14, CURSOR_COLOR);
+                }
+                image.text(x, y, &text, color);
+                let character = match bytes[index] {
+                    byte @ 0x20..0x7F => byte as char,
+                    _ => '.',
+                };
+                image.text(TEXT_X + column * 8, y, &character.to_string(), color);
+            }
+        }
+        image.text(
+            8,
+            HEIGHT - 16,
+            "TAB REGION  [ ] ROM BANK  - = SRAM BANK  M MAPPED  0-F EDIT",
+            LABEL_COLOR,
+        );
+        image
+    }
+}
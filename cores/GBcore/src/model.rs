@@ -0,0 +1,153 @@
+use crate::cartridge::{cart_info::CartridgeInfo, Cartridge};
+use crate::cpu::Registers;
+
+// Hardware the core emulates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Model {
+    DMG0,
+    DMG,
+    MGB,
+    SGB,
+    SGB2,
+    CGB,
+    AGB,
+}
+
+impl Model {
+    // CGB for cartridges that can use its features, DMG for everything else
+    pub fn for_cartridge(info: &CartridgeInfo) -> Model {
+        if info.cgb_support() {
+            Model::CGB
+        } else {
+            Model::DMG
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Model> {
+        match name.to_ascii_lowercase().as_str() {
+            "dmg0" => Some(Model::DMG0),
+            "dmg" => Some(Model::DMG),
+            "mgb" => Some(Model::MGB),
+            "sgb" => Some(Model::SGB),
+            "sgb2" => Some(Model::SGB2),
+            "cgb" => Some(Model::CGB),
+            "agb" => Some(Model::AGB),
+            _ => None,
+        }
+    }
+
+    pub fn is_cgb(&self) -> bool {
+        matches!(self, Model::CGB | Model::AGB)
+    }
+
+    // 256 bytes for the monochrome models, 2304 for CGB (the cartridge header
+    // at 0x100 - 0x1FF stays visible in between)
+    pub fn boot_rom_size(&self) -> usize {
+        if self.is_cgb() {
+            0x900
+        } else {
+            0x100
+        }
+    }
+
+    // CPU registers the boot ROM leaves behind when it jumps to 0x100
+    pub fn post_boot_registers(&self, cart: &Cartridge) -> Registers {
+        // DMG and MGB set H and C unless the header checksum is 0
+        let dmg_flags = if cart.info.header_checksum == 0 {
+            0x80
+        } else {
+            0xB0
+        };
+        let (a, f, b, c, d, e, h, l) = match self {
+            Model::DMG0 => (0x01, 0x00, 0xFF, 0x13, 0x00, 0xC1, 0x84, 0x03),
+            Model::DMG => (0x01, dmg_flags, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D),
+            Model::MGB => (0xFF, dmg_flags, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D),
+            Model::SGB => (0x01, 0x00, 0x00, 0x14, 0x00, 0x00, 0xC0, 0x60),
+            Model::SGB2 => (0xFF, 0x00, 0x00, 0x14, 0x00, 0x00, 0xC0, 0x60),
+            Model::CGB | Model::AGB => {
+                let agb = (*self == Model::AGB) as u8;
+                let f = if agb > 0 { 0x00 } else { 0x80 };
+                if cart.info.cgb_support() {
+                    (0x11, f, agb, 0x00, 0xFF, 0x56, 0x00, 0x0D)
+                } else {
+                    // the compatibility palette lookup leaves the title checksum in B
+                    let b = title_checksum(cart).wrapping_add(agb);
+                    let (h, l) = if b == 0x43 || b == 0x58 {
+                        (0x99, 0x1A)
+                    } else {
+                        (0x00, 0x7C)
+                    };
+                    (0x11, f, b, 0x00, 0x00, 0x08, h, l)
+                }
+            }
+        };
+        Registers {
+            a,
+            f,
+            b,
+            c,
+            d,
+            e,
+            h,
+            l,
+            sp: 0xFFFE,
+            pc: 0x100,
+        }
+    }
+
+    // internal 16 bit divider counter at 0x100, DIV is the upper byte
+    pub fn post_boot_div(&self) -> u16 {
+        match self {
+            Model::DMG0 => 0x182C,
+            Model::DMG | Model::MGB => 0xABCC,
+            Model::SGB | Model::SGB2 => 0xD85C,
+            Model::CGB | Model::AGB => 0x1EA0,
+        }
+    }
+
+    // IO registers the boot ROM leaves behind, written in this order
+    pub fn post_boot_io(&self) -> Vec<(usize, u8)> {
+        vec![
+            (0xFF0F, 0xE1),
+            // NR52 first, the APU ignores everything else while it's off
+            (0xFF26, 0x80),
+            (0xFF10, 0x80),
+            (0xFF11, 0xBF),
+            (0xFF12, 0xF3),
+            (0xFF13, 0xFF),
+            (0xFF14, 0x3F),
+            (0xFF16, 0x3F),
+            (0xFF17, 0x00),
+            (0xFF18, 0xFF),
+            (0xFF19, 0x3F),
+            (0xFF1A, 0x7F),
+            (0xFF1B, 0xFF),
+            (0xFF1C, 0x9F),
+            (0xFF1D, 0xFF),
+            (0xFF1E, 0x3F),
+            (0xFF20, 0xFF),
+            (0xFF21, 0x00),
+            (0xFF22, 0x00),
+            (0xFF23, 0x3F),
+            (0xFF24, 0x77),
+            (0xFF25, 0xF3),
+            (0xFF40, 0x91),
+            (0xFF47, 0xFC),
+        ]
+    }
+
+    // channel 1 is still flagged as playing after the boot sound, SGBs don't have one
+    pub fn boot_sound(&self) -> bool {
+        !matches!(self, Model::SGB | Model::SGB2)
+    }
+}
+
+fn title_checksum(cart: &Cartridge) -> u8 {
+    let new_licensee = (cart.read(0x144), cart.read(0x145));
+    let old_licensee = cart.read(0x14B);
+    if old_licensee == 0x01 || (old_licensee == 0x33 && new_licensee == (b'0', b'1')) {
+        (0x134..0x144).fold(0u8, |sum, address| sum.wrapping_add(cart.read(address)))
+    } else {
+        0
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+
+use crate::cartridge::Cartridge;
+use crate::cpu::TestResult;
+use crate::model::Model;
+use crate::GameBoy;
+
+// every Mooneye test is done within a few seconds
+static FRAME_BUDGET: u32 = 60 * 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Passed,
+    Failed,
+    Timeout,
+    Error(String),
+}
+
+pub struct RomReport {
+    pub subsystem: String,
+    pub name: String,
+    pub outcome: Outcome,
+}
+
+// The suffix of a ROM's name tells which hardware it was written for, e.g.
+// boot_regs-dmg0.gb or boot_div-cgbABCDE.gb. Suffixes naming several models
+// (GS, dmgABCmgb) run on a DMG, anything unknown goes by the header.
+pub fn model_for(name: &str) -> Option<Model> {
+    let suffix = name.rsplit_once('-')?.1;
+    match suffix {
+        "dmg0" => Some(Model::DMG0),
+        "mgb" => Some(Model::MGB),
+        "sgb2" => Some(Model::SGB2),
+        "sgb" | "S" => Some(Model::SGB),
+        "A" => Some(Model::AGB),
+        "C" => Some(Model::CGB),
+        s if s.starts_with("cgb") => Some(Model::CGB),
+        s if s.starts_with("dmg") || s.starts_with('G') => Some(Model::DMG),
+        _ => None,
+    }
+}
+
+// groups the suite's directories (acceptance/timer, emulator-only/mbc1 ...)
+fn subsystem_for(relative: &Path) -> String {
+    let directories: Vec<String> = relative
+        .parent()
+        .map(|parent| {
+            parent
+                .iter()
+                .map(|part| part.to_string_lossy().to_lowercase())
+                .collect()
+        })
+        .unwrap_or_default();
+    for directory in directories.iter().rev() {
+        match directory.as_str() {
+            "timer" => return "timer".to_string(),
+            "interrupts" => return "interrupts".to_string(),
+            "ppu" => return "PPU".to_string(),
+            d if d.starts_with("mbc") => return "MBC".to_string(),
+            "acceptance" | "emulator-only" | "misc" => {}
+            d => return d.to_string(),
+        }
+    }
+    "misc".to_string()
+}
+
+// Cartridge types that aren't done yet panic, that's one ROM's error and
+// not the end of the run
+pub fn run_rom(path: &Path) -> Outcome {
+    match panic::catch_unwind(|| run_rom_unchecked(path)) {
+        Ok(outcome) => outcome,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            Outcome::Error(format!("panicked: {}", message))
+        }
+    }
+}
+
+fn run_rom_unchecked(path: &Path) -> Outcome {
+    let cartridge = match Cartridge::from_path(&path.to_string_lossy()) {
+        Ok(cartridge) => cartridge,
+        Err(err) => return Outcome::Error(err),
+    };
+    let name = path.file_stem().unwrap_or_default().to_string_lossy();
+    let model = model_for(&name).unwrap_or_else(|| Model::for_cartridge(&cartridge.info));
+    let mut gameboy = GameBoy::with_model(cartridge, model);
+    gameboy.detect_test_result(true);
+    for _ in 0..FRAME_BUDGET {
+        if let Err(err) = gameboy.run_frame() {
+            return Outcome::Error(err);
+        }
+        gameboy.audio_samples();
+        match gameboy.test_result() {
+            Some(TestResult::Passed) => return Outcome::Passed,
+            Some(TestResult::Failed) => return Outcome::Failed,
+            None => {}
+        }
+    }
+    Outcome::Timeout
+}
+
+fn collect_roms(directory: &Path, roms: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = fs::read_dir(directory).map_err(|err| err.to_string())?;
+    for entry in entries {
+        let path = entry.map_err(|err| err.to_string())?.path();
+        if path.is_dir() {
+            collect_roms(&path, roms)?;
+        } else if path.extension().is_some_and(|extension| extension == "gb") {
+            roms.push(path);
+        }
+    }
+    Ok(())
+}
+
+// Runs every .gb file below `directory`, in name order
+pub fn run_dir(directory: &Path) -> Result<Vec<RomReport>, String> {
+    let mut roms = Vec::new();
+    collect_roms(directory, &mut roms)?;
+    roms.sort();
+    // Panics end up in the table, they shouldn't be printed in between.
+    // Other threads still get the usual message.
+    let hook: Arc<dyn Fn(&panic::PanicHookInfo) + Send + Sync> = Arc::from(panic::take_hook());
+    let runner = thread::current().id();
+    let quiet_hook = Arc::clone(&hook);
+    panic::set_hook(Box::new(move |info| {
+        if thread::current().id() != runner {
+            quiet_hook(info);
+        }
+    }));
+    let reports = roms
+        .iter()
+        .map(|path| {
+            let relative = path.strip_prefix(directory).unwrap_or(path);
+            RomReport {
+                subsystem: subsystem_for(relative),
+                name: relative.to_string_lossy().to_string(),
+                outcome: run_rom(path),
+            }
+        })
+        .collect();
+    panic::set_hook(Box::new(move |info| hook(info)));
+    Ok(reports)
+}
+
+pub fn print_table(reports: &[RomReport]) {
+    let mut subsystems: BTreeMap<&str, Vec<&RomReport>> = BTreeMap::new();
+    for report in reports {
+        subsystems
+            .entry(&report.subsystem)
+            .or_default()
+            .push(report);
+    }
+
+    for (subsystem, reports) in &subsystems {
+        println!("{}", subsystem);
+        for report in reports {
+            let outcome = match &report.outcome {
+                Outcome::Passed => "pass".to_string(),
+                Outcome::Failed => "FAIL".to_string(),
+                Outcome::Timeout => "TIMEOUT".to_string(),
+                Outcome::Error(err) => format!("ERROR {}", err),
+            };
+            println!("  {: <8} {}", outcome, report.name);
+        }
+    }
+
+    println!();
+    println!("{: <14} {: >6} {: >6}", "subsystem", "passed", "total");
+    for (subsystem, reports) in &subsystems {
+        let passed = reports
+            .iter()
+            .filter(|report| report.outcome == Outcome::Passed)
+            .count();
+        println!("{: <14} {: >6} {: >6}", subsystem, passed, reports.len());
+    }
+    let passed = reports
+        .iter()
+        .filter(|report| report.outcome == Outcome::Passed)
+        .count();
+    println!("{: <14} {: >6} {: >6}", "all", passed, reports.len());
+}
@@ -1,3 +1,4 @@
+#[derive(Clone, Copy)]
 pub enum FetchState {
     TILE,
     DATA0,
@@ -5,3 +6,25 @@ pub enum FetchState {
     SLEEP,
     PUSH,
 }
+
+impl FetchState {
+    pub fn to_u8(self) -> u8 {
+        match self {
+            FetchState::TILE => 0,
+            FetchState::DATA0 => 1,
+            FetchState::DATA1 => 2,
+            FetchState::SLEEP => 3,
+            FetchState::PUSH => 4,
+        }
+    }
+    pub fn from_u8(value: u8) -> Result<FetchState, String> {
+        match value {
+            0 => Ok(FetchState::TILE),
+            1 => Ok(FetchState::DATA0),
+            2 => Ok(FetchState::DATA1),
+            3 => Ok(FetchState::SLEEP),
+            4 => Ok(FetchState::PUSH),
+            _ => Err(format!("Invalid fetch state {value}")),
+        }
+    }
+}
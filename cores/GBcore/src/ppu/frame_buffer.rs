@@ -0,0 +1,106 @@
+use super::{XRES, YRES};
+use crate::io::lcd::COLORS;
+use crate::state::{SaveState, StateReader, StateWriter};
+
+pub const FRAME_WIDTH: usize = XRES as usize;
+pub const FRAME_HEIGHT: usize = YRES as usize;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Color {
+        Color { r, g, b, a: 0xFF }
+    }
+
+    // 0xRRGGBBAA, the layout SDL calls RGBA8888
+    pub fn to_rgba8888(&self) -> u32 {
+        u32::from_be_bytes([self.r, self.g, self.b, self.a])
+    }
+
+    // 2-bit DMG shade (0 = lightest, 3 = darkest). Colors outside of the DMG
+    // palette are bucketed by luminance.
+    pub fn shade(&self) -> u8 {
+        if let Some(index) = COLORS.iter().position(|c| c == self) {
+            return index as u8;
+        }
+        let luma = (self.r as u32 * 299 + self.g as u32 * 587 + self.b as u32 * 114) / 1000;
+        3 - (luma / 64) as u8
+    }
+}
+
+#[derive(Clone)]
+pub struct FrameBuffer {
+    pixels: [Color; FRAME_WIDTH * FRAME_HEIGHT],
+}
+
+impl Default for FrameBuffer {
+    fn default() -> Self {
+        FrameBuffer::new()
+    }
+}
+
+impl FrameBuffer {
+    pub fn new() -> Self {
+        FrameBuffer {
+            pixels: [COLORS[0]; FRAME_WIDTH * FRAME_HEIGHT],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        FRAME_WIDTH
+    }
+    pub fn height(&self) -> usize {
+        FRAME_HEIGHT
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Color {
+        self.pixels[x + y * FRAME_WIDTH]
+    }
+    pub fn set(&mut self, x: usize, y: usize, color: Color) {
+        self.pixels[x + y * FRAME_WIDTH] = color;
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    // R, G, B, A bytes for every pixel, row by row
+    pub fn to_rgba8888(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.pixels.len() * 4);
+        for pixel in self.pixels.iter() {
+            bytes.extend_from_slice(&[pixel.r, pixel.g, pixel.b, pixel.a]);
+        }
+        bytes
+    }
+
+    // one 2-bit shade per pixel, row by row
+    pub fn to_shades(&self) -> Vec<u8> {
+        self.pixels.iter().map(|pixel| pixel.shade()).collect()
+    }
+}
+
+impl SaveState for FrameBuffer {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bytes(&self.to_rgba8888());
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        let mut rgba = [0; 4];
+        for pixel in self.pixels.iter_mut() {
+            reader.read_bytes(&mut rgba)?;
+            *pixel = Color {
+                r: rgba[0],
+                g: rgba[1],
+                b: rgba[2],
+                a: rgba[3],
+            };
+        }
+        Ok(())
+    }
+}
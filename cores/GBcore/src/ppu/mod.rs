@@ -2,20 +2,19 @@
 // pub mod ppu_pipeline;
 // pub mod ppu_sm;
 mod fetch_state;
+pub mod frame_buffer;
 mod oam_entry;
 mod pipeline;
 mod pixel_fifo;
 mod state_machine;
-use std::{
-    rc::Rc,
-    time::{Duration, Instant},
-};
+use std::rc::Rc;
 
 use crate::interrupts::InterruptType;
-use crate::io::lcd::{Mode, COLORS, LCD};
+use crate::io::lcd::{Mode, LCD};
+use crate::state::{SaveState, StateReader, StateWriter};
+pub use frame_buffer::{Color, FrameBuffer};
 use oam_entry::OamEntry;
 use pixel_fifo::PixelFifo;
-use sdl2::pixels::Color;
 
 pub static LINES_PER_FRAME: u8 = 154;
 pub static TICKS_PER_LINE: u16 = 456;
@@ -23,17 +22,22 @@ pub static YRES: u8 = 144;
 pub static XRES: u8 = 160;
 pub struct PPU {
     pub oam_ram: [OamEntry; 40],
-    pub vram: [u8; 0x2000],
+    // two 8KB banks, the second one only exists on CGB
+    pub vram: [u8; 0x4000],
+    pub vram_bank: usize,
+    pub cgb_mode: bool,
     pub window_line: u16,
     pub lcd: LCD,
     pub line_entries: Vec<OamEntry>,
     pub fetched_entries: Vec<OamEntry>,
     pub line_ticks: u16,
-    pub video_buffer: [Color; 144 * 160],
+    pub video_buffer: FrameBuffer,
     pub pf_control: PixelFifo,
     pub have_update: bool,
-    pub last_frame_end: Instant,
-    pub frame_duration: Duration,
+    // set when HBlank starts, HBlank DMA picks it up
+    pub hblank_event: bool,
+    // set when VBlank starts, for the cheats
+    pub vblank_event: bool,
 
     pub request_interrupt: Option<Rc<dyn Fn(InterruptType)>>,
 }
@@ -42,17 +46,19 @@ impl PPU {
     pub fn new() -> PPU {
         PPU {
             oam_ram: [OamEntry::empty(); 40],
-            vram: [0; 0x2000],
+            vram: [0; 0x4000],
+            vram_bank: 0,
+            cgb_mode: false,
             lcd: LCD::new(),
             line_ticks: 0,
             window_line: 0,
             line_entries: vec![],
             fetched_entries: vec![],
-            video_buffer: [COLORS[0]; 144 * 160],
+            video_buffer: FrameBuffer::new(),
             pf_control: PixelFifo::new(),
             have_update: false,
-            last_frame_end: Instant::now(),
-            frame_duration: Duration::from_secs_f64(1.0 / 60.0),
+            hblank_event: false,
+            vblank_event: false,
 
             request_interrupt: None,
         }
@@ -85,6 +91,14 @@ impl PPU {
         }
     }
 
+    pub fn take_hblank_event(&mut self) -> bool {
+        std::mem::take(&mut self.hblank_event)
+    }
+
+    pub fn take_vblank_event(&mut self) -> bool {
+        std::mem::take(&mut self.vblank_event)
+    }
+
     pub fn oam_write(&mut self, address: usize, value: u8) {
         let adjusted_address = if address >= 0xFE00 {
             address.wrapping_sub(0xFE00)
@@ -131,13 +145,84 @@ impl PPU {
         return &self.oam_ram[index];
     }
     pub fn vram_write(&mut self, address: usize, value: u8) {
-        self.vram[address - 0x8000] = value;
+        self.vram[self.vram_bank * 0x2000 + address - 0x8000] = value;
     }
     pub fn vram_read(&self, address: usize) -> u8 {
-        self.vram[address - 0x8000]
+        self.vram_read_bank(self.vram_bank, address)
+    }
+    pub fn vram_read_bank(&self, bank: usize, address: usize) -> u8 {
+        self.vram[bank * 0x2000 + address - 0x8000]
+    }
+
+    // VBK
+    pub fn vbk_read(&self) -> u8 {
+        if self.cgb_mode {
+            0xFE | self.vram_bank as u8
+        } else {
+            0xFF
+        }
+    }
+    pub fn vbk_write(&mut self, value: u8) {
+        if self.cgb_mode {
+            self.vram_bank = (value & 1) as usize;
+        }
     }
 
     pub fn get_video_buffer(&self) -> Vec<Color> {
-        Vec::from(self.video_buffer)
+        self.video_buffer.pixels().to_vec()
+    }
+}
+
+fn write_entries(writer: &mut StateWriter, entries: &[OamEntry]) {
+    writer.write_u32(entries.len() as u32);
+    for entry in entries {
+        writer.write_bytes(&entry.to_bytes());
+    }
+}
+
+fn read_entries(reader: &mut StateReader, entries: &mut Vec<OamEntry>) -> Result<(), String> {
+    entries.clear();
+    for _ in 0..reader.read_u32()? {
+        let mut bytes = [0; 4];
+        reader.read_bytes(&mut bytes)?;
+        entries.push(OamEntry::from_bytes(bytes));
+    }
+    Ok(())
+}
+
+impl SaveState for PPU {
+    fn save_state(&self, writer: &mut StateWriter) {
+        for entry in self.oam_ram.iter() {
+            writer.write_bytes(&entry.to_bytes());
+        }
+        writer.write_bytes(&self.vram);
+        writer.write_usize(self.vram_bank);
+        writer.write_u16(self.window_line);
+        self.lcd.save_state(writer);
+        write_entries(writer, &self.line_entries);
+        write_entries(writer, &self.fetched_entries);
+        writer.write_u16(self.line_ticks);
+        self.video_buffer.save_state(writer);
+        self.pf_control.save_state(writer);
+        writer.write_bool(self.have_update);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        for entry in self.oam_ram.iter_mut() {
+            let mut bytes = [0; 4];
+            reader.read_bytes(&mut bytes)?;
+            *entry = OamEntry::from_bytes(bytes);
+        }
+        reader.read_bytes(&mut self.vram)?;
+        self.vram_bank = reader.read_usize()? & 1;
+        self.window_line = reader.read_u16()?;
+        self.lcd.load_state(reader)?;
+        read_entries(reader, &mut self.line_entries)?;
+        read_entries(reader, &mut self.fetched_entries)?;
+        self.line_ticks = reader.read_u16()?;
+        self.video_buffer.load_state(reader)?;
+        self.pf_control.load_state(reader)?;
+        self.have_update = reader.read_bool()?;
+        Ok(())
     }
 }
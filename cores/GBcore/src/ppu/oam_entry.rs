@@ -14,6 +14,17 @@ impl OamEntry {
             attributes: ((value >> 24) & 0xFF) as u8,
         }
     }
+    pub fn to_bytes(self) -> [u8; 4] {
+        [self.y, self.x, self.tile_idx, self.attributes]
+    }
+    pub fn from_bytes(bytes: [u8; 4]) -> Self {
+        OamEntry {
+            y: bytes[0],
+            x: bytes[1],
+            tile_idx: bytes[2],
+            attributes: bytes[3],
+        }
+    }
     pub fn empty() -> OamEntry {
         OamEntry {
             y: 0,
@@ -43,4 +54,10 @@ impl OamEntry {
     pub fn palette(&self) -> u8 {
         self.attributes >> 4 & 1
     }
+    pub fn cgb_bank(&self) -> usize {
+        (self.attributes >> 3 & 1) as usize
+    }
+    pub fn cgb_palette(&self) -> u8 {
+        self.attributes & 0b111
+    }
 }
@@ -1,6 +1,4 @@
-use sdl2::pixels::Color;
-
-use super::{fetch_state::FetchState, PPU, XRES, YRES};
+use super::{fetch_state::FetchState, Color, PPU, XRES, YRES};
 
 impl PPU {
     pub fn pipeline_fifo_reset(&mut self) {
@@ -15,22 +13,28 @@ impl PPU {
         let x: i32 = (self.pf_control.fetch_x as i32)
             .wrapping_sub(8 - (self.lcd.scroll_x.wrapping_rem(8) as i32));
 
+        let attributes = self.pf_control.bgw_attributes;
         for i in 0..8 {
-            let bit = 7 - i;
+            let x_flipped = self.cgb_mode && attributes & 0x20 > 0;
+            let bit = if x_flipped { i } else { 7 - i };
 
             let b1 = self.pf_control.bgw_fetch_data[1];
             let b2 = self.pf_control.bgw_fetch_data[2];
             let low = b1 >> bit & 1;
             let hi = (b2 >> bit & 1) << 1;
             let col_index = (hi | low) as usize;
-            let mut col = self.lcd.bg_colors[col_index];
+            let mut col = if self.cgb_mode {
+                self.lcd.cgb_bg_color(attributes, col_index)
+            } else {
+                self.lcd.bg_colors[col_index]
+            };
 
-            if self.lcd.lcdc_bgw_enabled() {
+            if !self.cgb_mode && self.lcd.lcdc_bgw_enabled() {
                 col = self.lcd.bg_colors[0];
             }
 
             if self.lcd.lcdc_obj_enabled() {
-                col = self.fetch_sprite_pixels(col, hi | low);
+                col = self.fetch_sprite_pixels(col, hi | low, attributes);
             }
             if x >= 0 {
                 self.pixel_fifo_push(col);
@@ -41,7 +45,7 @@ impl PPU {
         return true;
     }
 
-    fn fetch_sprite_pixels(&self, col: Color, bg_col_index: u8) -> Color {
+    fn fetch_sprite_pixels(&self, col: Color, bg_col_index: u8, bg_attributes: u8) -> Color {
         let mut result_color: Color = col;
         for (index, entry) in self.fetched_entries.iter().enumerate() {
             let cur_x_pos = self.pf_control.fifo_x as i32;
@@ -70,8 +74,16 @@ impl PPU {
                 //transparent pixel
                 continue;
             }
-            if !entry.draw_under_bg() || bg_col_index == 0 {
-                result_color = if entry.palette() == 0 {
+            // on CGB the BG map attributes can claim priority as well, unless LCDC bit 0 overrides both
+            let bg_priority = if self.cgb_mode {
+                self.lcd.lcdc_bg_priority() && (entry.draw_under_bg() || bg_attributes & 0x80 > 0)
+            } else {
+                entry.draw_under_bg()
+            };
+            if !bg_priority || bg_col_index == 0 {
+                result_color = if self.cgb_mode {
+                    self.lcd.cgb_obj_color(entry.cgb_palette(), col_idx)
+                } else if entry.palette() == 0 {
                     self.lcd.obj0_colors[col_idx]
                 } else {
                     self.lcd.obj1_colors[col_idx]
@@ -95,9 +107,10 @@ impl PPU {
             FetchState::TILE => {
                 self.fetched_entries.clear();
 
-                if !self.lcd.lcdc_bgw_enabled() {
+                // on CGB bit 0 only takes priority away from the BG, it is still fetched
+                if self.cgb_mode || !self.lcd.lcdc_bgw_enabled() {
                     let bg_map_start = self.lcd.lcdc_bg_map_area();
-                    self.pf_control.bgw_fetch_data[0] = self.vram_read(
+                    self.pipeline_load_map_entry(
                         bg_map_start
                             + (self.pf_control.map_x as usize / 8)
                             + ((self.pf_control.map_y as usize / 8) * 32),
@@ -119,9 +132,9 @@ impl PPU {
             FetchState::DATA0 => {
                 let idx = self.lcd.lcdc_bg_data_area()
                     + ((self.pf_control.bgw_fetch_data[0] as usize) * 16)
-                    + self.pf_control.tile_y as usize;
+                    + self.bgw_tile_row();
 
-                self.pf_control.bgw_fetch_data[1] = self.vram_read(idx);
+                self.pf_control.bgw_fetch_data[1] = self.vram_read_bank(self.bgw_tile_bank(), idx);
 
                 self.pipeline_load_sprite_data(0);
 
@@ -129,10 +142,11 @@ impl PPU {
             }
             FetchState::DATA1 => {
                 let data_start = self.lcd.lcdc_bg_data_area();
-                self.pf_control.bgw_fetch_data[2] = self.vram_read(
+                self.pf_control.bgw_fetch_data[2] = self.vram_read_bank(
+                    self.bgw_tile_bank(),
                     data_start
                         + ((self.pf_control.bgw_fetch_data[0] as usize) * 16)
-                        + self.pf_control.tile_y as usize
+                        + self.bgw_tile_row()
                         + 1,
                 );
 
@@ -158,6 +172,29 @@ impl PPU {
         }
     }
 
+    // tile index from bank 0 and, in CGB Mode, its attributes from bank 1
+    fn pipeline_load_map_entry(&mut self, address: usize) {
+        self.pf_control.bgw_fetch_data[0] = self.vram_read_bank(0, address);
+        self.pf_control.bgw_attributes = if self.cgb_mode {
+            self.vram_read_bank(1, address)
+        } else {
+            0
+        };
+    }
+
+    fn bgw_tile_bank(&self) -> usize {
+        (self.pf_control.bgw_attributes >> 3 & 1) as usize
+    }
+
+    // byte offset of the current row inside the tile, mirrored by the CGB Y flip attribute
+    fn bgw_tile_row(&self) -> usize {
+        if self.pf_control.bgw_attributes & 0x40 > 0 {
+            14 - self.pf_control.tile_y as usize
+        } else {
+            self.pf_control.tile_y as usize
+        }
+    }
+
     pub fn pipeline_process(&mut self) {
         self.pf_control.map_y = self.lcd.ly.wrapping_add(self.lcd.scroll_y);
         self.pf_control.map_x = self.pf_control.fetch_x.wrapping_add(self.lcd.scroll_x);
@@ -181,9 +218,11 @@ impl PPU {
         if self.pf_control.pixel_fifo.len() > 8 {
             if let Some(pixel) = self.pixel_fifo_pop() {
                 if self.pf_control.line_x >= self.lcd.scroll_x.wrapping_rem(8) {
-                    let idx =
-                        self.pf_control.pushed_x as usize + (self.lcd.ly as usize * XRES as usize);
-                    self.video_buffer[idx] = pixel;
+                    self.video_buffer.set(
+                        self.pf_control.pushed_x as usize,
+                        self.lcd.ly as usize,
+                        pixel,
+                    );
                     self.pf_control.pushed_x += 1;
                 }
             }
@@ -218,8 +257,11 @@ impl PPU {
             if obj_height == 16 {
                 tile_idx &= !(1);
             }
-            self.pf_control.fetch_entry_data[(index * 2) + offset] =
-                self.vram_read(0x8000 + (tile_idx as usize * 16) + ty as usize + offset);
+            let bank = if self.cgb_mode { entry.cgb_bank() } else { 0 };
+            self.pf_control.fetch_entry_data[(index * 2) + offset] = self.vram_read_bank(
+                bank,
+                0x8000 + (tile_idx as usize * 16) + ty as usize + offset,
+            );
         }
     }
 
@@ -240,7 +282,10 @@ impl PPU {
                 self.line_entries.push(entry);
             }
         }
-        self.line_entries.sort_by(|a, b| a.x.cmp(&b.x))
+        // the CGB ranks objects by their OAM position instead of their X coordinate
+        if !self.cgb_mode {
+            self.line_entries.sort_by(|a, b| a.x.cmp(&b.x))
+        }
     }
 
     fn pipeline_load_window_tile(&mut self) {
@@ -259,7 +304,7 @@ impl PPU {
             let ly = self.lcd.ly as usize;
             if ly as usize >= win_y && ly < win_y + x_res {
                 let window_tile_y = self.window_line / 8;
-                self.pf_control.bgw_fetch_data[0] = self.vram_read(
+                self.pipeline_load_map_entry(
                     self.lcd.lcdc_window_tile_map_area()
                         + (self
                             .pf_control
@@ -1,8 +1,7 @@
 use std::collections::LinkedList;
 
-use sdl2::pixels::Color;
-
-use super::fetch_state::FetchState;
+use super::{fetch_state::FetchState, Color};
+use crate::state::{SaveState, StateReader, StateWriter};
 
 pub struct PixelFifo {
     pub cur_fetch_state: FetchState,
@@ -11,6 +10,8 @@ pub struct PixelFifo {
     pub pushed_x: u8,
     pub fetch_x: u8,
     pub bgw_fetch_data: [u8; 3],
+    // CGB BG map attributes of the fetched tile: palette, bank, flips and priority
+    pub bgw_attributes: u8,
     pub fetch_entry_data: [u8; 6], //oam data.: usize,
     pub map_y: u8,
     pub map_x: u8,
@@ -26,6 +27,7 @@ impl PixelFifo {
             pushed_x: 0,
             fetch_x: 0,
             bgw_fetch_data: [0; 3],
+            bgw_attributes: 0,
             fetch_entry_data: [0; 6],
             map_y: 0,
             map_x: 0,
@@ -42,3 +44,49 @@ impl PixelFifo {
         self.fifo_x = 0;
     }
 }
+
+impl SaveState for PixelFifo {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.cur_fetch_state.to_u8());
+        writer.write_u32(self.pixel_fifo.len() as u32);
+        for color in self.pixel_fifo.iter() {
+            writer.write_bytes(&[color.r, color.g, color.b, color.a]);
+        }
+        writer.write_u8(self.line_x);
+        writer.write_u8(self.pushed_x);
+        writer.write_u8(self.fetch_x);
+        writer.write_bytes(&self.bgw_fetch_data);
+        writer.write_u8(self.bgw_attributes);
+        writer.write_bytes(&self.fetch_entry_data);
+        writer.write_u8(self.map_y);
+        writer.write_u8(self.map_x);
+        writer.write_u8(self.tile_y);
+        writer.write_u8(self.fifo_x);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.cur_fetch_state = FetchState::from_u8(reader.read_u8()?)?;
+        self.pixel_fifo.clear();
+        for _ in 0..reader.read_u32()? {
+            let mut rgba = [0; 4];
+            reader.read_bytes(&mut rgba)?;
+            self.pixel_fifo.push_back(Color {
+                r: rgba[0],
+                g: rgba[1],
+                b: rgba[2],
+                a: rgba[3],
+            });
+        }
+        self.line_x = reader.read_u8()?;
+        self.pushed_x = reader.read_u8()?;
+        self.fetch_x = reader.read_u8()?;
+        reader.read_bytes(&mut self.bgw_fetch_data)?;
+        self.bgw_attributes = reader.read_u8()?;
+        reader.read_bytes(&mut self.fetch_entry_data)?;
+        self.map_y = reader.read_u8()?;
+        self.map_x = reader.read_u8()?;
+        self.tile_y = reader.read_u8()?;
+        self.fifo_x = reader.read_u8()?;
+        Ok(())
+    }
+}
@@ -1,8 +1,3 @@
-use std::{
-    thread,
-    time::{Duration, Instant},
-};
-
 use super::{LINES_PER_FRAME, PPU, TICKS_PER_LINE, XRES, YRES};
 
 use crate::{
@@ -57,6 +52,7 @@ impl PPU {
         if self.pf_control.pushed_x >= XRES {
             self.pipeline_fifo_reset();
             self.lcd.lcds_mode_set(Mode::HBlank);
+            self.hblank_event = true;
             if self.lcd.lcds_stat_int(StatType::HBLANK) {
                 if let Some(request_interrupt) = &self.request_interrupt {
                     request_interrupt(InterruptType::LCD_STAT);
@@ -95,15 +91,8 @@ impl PPU {
                     }
                 }
 
-                let sleep_time: Duration;
-                let elapsed = self.last_frame_end.elapsed(); //add deduction period compensating the real hardware delay
-                if elapsed < self.frame_duration {
-                    sleep_time = self.frame_duration - elapsed;
-                    thread::sleep(sleep_time);
-                }
-
-                self.last_frame_end = Instant::now();
                 self.have_update = true;
+                self.vblank_event = true;
             } else {
                 self.lcd.lcds_mode_set(Mode::OAM);
             }
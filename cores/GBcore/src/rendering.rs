@@ -1,45 +1,183 @@
 // use crate::GameBoyEngine::
-use super::{input::Input, io::lcd::COLORS};
+use super::{
+    bus::Bus,
+    cheat_list::CheatList,
+    input::Input,
+    io::lcd::COLORS,
+    memory_viewer::MemoryViewer,
+    ppu::{self, FrameBuffer, PPU},
+    GameBoy,
+};
 
-use sdl2::{event::Event, keyboard::Keycode, pixels::Color, pixels::PixelFormatEnum, rect::Point};
+use sdl2::{
+    event::{Event, WindowEvent},
+    keyboard::{Keycode, Mod},
+    pixels::Color,
+    pixels::PixelFormatEnum,
+    rect::Point,
+};
 pub static SCALE: u32 = 3;
 
+fn sdl_color(color: ppu::Color) -> Color {
+    Color::RGBA(color.r, color.g, color.b, color.a)
+}
+
+// Keyboard layout of one player
+pub struct KeyMap {
+    pub up: Keycode,
+    pub down: Keycode,
+    pub left: Keycode,
+    pub right: Keycode,
+    pub a: Keycode,
+    pub b: Keycode,
+    pub start: Keycode,
+    pub select: Keycode,
+}
+
+impl KeyMap {
+    pub fn single_player() -> Self {
+        KeyMap {
+            up: Keycode::UP,
+            down: Keycode::DOWN,
+            left: Keycode::LEFT,
+            right: Keycode::RIGHT,
+            a: Keycode::Z,
+            b: Keycode::X,
+            start: Keycode::SPACE,
+            select: Keycode::V,
+        }
+    }
+
+    // left half of the keyboard for the left screen
+    pub fn left_player() -> Self {
+        KeyMap {
+            up: Keycode::W,
+            down: Keycode::S,
+            left: Keycode::A,
+            right: Keycode::D,
+            a: Keycode::G,
+            b: Keycode::F,
+            start: Keycode::NUM_1,
+            select: Keycode::NUM_2,
+        }
+    }
+
+    // right half of the keyboard for the right screen
+    pub fn right_player() -> Self {
+        KeyMap {
+            up: Keycode::UP,
+            down: Keycode::DOWN,
+            left: Keycode::LEFT,
+            right: Keycode::RIGHT,
+            a: Keycode::PERIOD,
+            b: Keycode::COMMA,
+            start: Keycode::RETURN,
+            select: Keycode::RSHIFT,
+        }
+    }
+
+    fn apply(&self, key: Keycode, pressed: bool, input: &mut Input) {
+        let button = match key {
+            k if k == self.up => &mut input.Up,
+            k if k == self.down => &mut input.Down,
+            k if k == self.left => &mut input.Left,
+            k if k == self.right => &mut input.Right,
+            k if k == self.a => &mut input.A,
+            k if k == self.b => &mut input.B,
+            k if k == self.start => &mut input.Start,
+            k if k == self.select => &mut input.Select,
+            _ => return,
+        };
+        *button = pressed;
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "D-Pad - {} {} {} {}\nA - {} key\nB - {} key\nStart - {}\nSelect - {}",
+            self.up.name(),
+            self.left.name(),
+            self.down.name(),
+            self.right.name(),
+            self.a.name(),
+            self.b.name(),
+            self.start.name(),
+            self.select.name()
+        )
+    }
+}
+
 pub struct Renderer {
     // pub tick: u64,
-    last_input: Input,
+    // one screen side by side per key map
+    keymaps: Vec<KeyMap>,
+    last_inputs: Vec<Input>,
     pub exited: bool,
+    pub rewinding: bool,
+    // F12 was pressed, the frontend clears it once the debugger is open
+    pub debug_requested: bool,
+    sdl_context: Option<sdl2::Sdl>,
     event_pump: Option<sdl2::EventPump>,
     canvas: Option<sdl2::render::Canvas<sdl2::video::Window>>,
+    // the VRAM viewer, F11 opens and closes it
+    debug_canvas: Option<sdl2::render::Canvas<sdl2::video::Window>>,
+    memory_viewer: Option<MemoryViewer>,
+    cheat_list: Option<CheatList>,
 }
 
 impl Renderer {
-    pub fn new() -> Self {
+    pub fn new(vsync: bool) -> Self {
+        let renderer = Renderer::with_screens(vsync, vec![KeyMap::single_player()]);
+        println!("Rewind - Backspace (hold)");
+        println!("Cheats - F9");
+        println!("Memory viewer - F10");
+        println!("VRAM viewer - F11");
+        println!("Debugger - F12 (in the terminal)");
+        renderer
+    }
+
+    pub fn with_screens(vsync: bool, keymaps: Vec<KeyMap>) -> Self {
         let mut renderer = Renderer {
-            last_input: Input::new(),
+            last_inputs: keymaps.iter().map(|_| Input::new()).collect(),
+            keymaps,
             exited: false,
+            rewinding: false,
+            debug_requested: false,
+            sdl_context: None,
             event_pump: None,
             canvas: None,
+            debug_canvas: None,
+            memory_viewer: None,
+            cheat_list: None,
             // imgbuf: image::ImageBuffer::new<>(160, 144),
         };
-        _ = renderer.init();
+        _ = renderer.init(vsync);
 
-        println!(
-            "Controls: 
-        D-Pad - Arrows\nA - Z key\nB - X key\nStart - Space\nSelect - V"
-        );
+        for (screen, keymap) in renderer.keymaps.iter().enumerate() {
+            println!("Controls (screen {}):\n{}", screen + 1, keymap.describe());
+        }
         renderer
     }
 
-    pub fn init(&mut self) -> Result<(), String> {
+    pub fn init(&mut self, vsync: bool) -> Result<(), String> {
         let sdl_context = sdl2::init()?;
         let video_subsystem = sdl_context.video()?;
         let window = video_subsystem
-            .window("Rustboy", 160 * SCALE, 144 * SCALE)
+            .window(
+                "Rustboy",
+                160 * SCALE * self.keymaps.len() as u32,
+                144 * SCALE,
+            )
             .position_centered()
             .opengl()
             .build()
             .map_err(|e| e.to_string())?;
-        self.canvas = Some(window.into_canvas().build().map_err(|e| e.to_string())?);
+        let canvas = window.into_canvas();
+        let canvas = if vsync {
+            canvas.present_vsync()
+        } else {
+            canvas
+        };
+        self.canvas = Some(canvas.build().map_err(|e| e.to_string())?);
         if let Some(canvas) = &mut self.canvas {
             _ = canvas.set_scale(3.0, 3.0);
             canvas.set_draw_color(Color::BLACK);
@@ -54,24 +192,95 @@ impl Renderer {
             //     .map_err(|e| e.to_string())?;
         }
         self.event_pump = Some(sdl_context.event_pump()?);
+        self.sdl_context = Some(sdl_context);
         Ok(())
     }
 
-    pub fn update(&mut self, buffer: Vec<Color>) -> Option<&Input> {
+    pub fn audio(&self) -> Result<sdl2::AudioSubsystem, String> {
+        match &self.sdl_context {
+            Some(sdl_context) => sdl_context.audio(),
+            None => Err("SDL is not initialized".to_string()),
+        }
+    }
+
+    pub fn update(&mut self, buffer: &FrameBuffer) -> Option<Input> {
+        self.update_screens(&[buffer]).remove(0)
+    }
+
+    // draws one buffer per screen, returns the new input of every screen whose keys changed
+    pub fn update_screens(&mut self, buffers: &[&FrameBuffer]) -> Vec<Option<Input>> {
         if let Some(canvas) = &mut self.canvas {
-            canvas.set_draw_color(COLORS[0]);
+            canvas.set_draw_color(sdl_color(COLORS[0]));
             canvas.clear();
         }
 
-        self.draw_main(buffer);
-        // self.update_debug_window();
+        for (screen, buffer) in buffers.iter().enumerate() {
+            self.draw_main(buffer, 160 * screen as i32);
+        }
         if let Some(canvas) = &mut self.canvas {
             canvas.present();
         }
-        let mut new_input = self.last_input.clone();
+        let mut new_inputs = self.last_inputs.clone();
+        let debug_window = self
+            .debug_canvas
+            .as_ref()
+            .map(|canvas| canvas.window().id());
+        let memory_window = self.memory_viewer.as_ref().map(MemoryViewer::window_id);
+        let cheat_window = self.cheat_list.as_ref().map(CheatList::window_id);
+        let mut toggle_debug_window = false;
+        let mut toggle_memory_window = false;
+        let mut toggle_cheat_window = false;
         if let Some(event_pump) = &mut self.event_pump {
             for event in event_pump.poll_iter() {
                 match event {
+                    // with the viewer open closing a window doesn't quit
+                    Event::Window {
+                        window_id,
+                        win_event: WindowEvent::Close,
+                        ..
+                    } => {
+                        if Some(window_id) == debug_window {
+                            toggle_debug_window = true;
+                        } else if Some(window_id) == memory_window {
+                            toggle_memory_window = true;
+                        } else if Some(window_id) == cheat_window {
+                            toggle_cheat_window = true;
+                        } else {
+                            self.exited = true;
+                            break;
+                        }
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F10),
+                        repeat: false,
+                        ..
+                    } => toggle_memory_window = true,
+                    // the memory viewer takes the keys while it has focus
+                    Event::KeyDown {
+                        window_id,
+                        keycode: Some(key),
+                        keymod,
+                        ..
+                    } if Some(window_id) == memory_window => {
+                        if let Some(viewer) = &mut self.memory_viewer {
+                            viewer.key(key, keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD));
+                        }
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F9),
+                        repeat: false,
+                        ..
+                    } => toggle_cheat_window = true,
+                    // and so does the cheat list
+                    Event::KeyDown {
+                        window_id,
+                        keycode: Some(key),
+                        ..
+                    } if Some(window_id) == cheat_window => {
+                        if let Some(cheat_list) = &mut self.cheat_list {
+                            cheat_list.key(key);
+                        }
+                    }
                     Event::Quit { .. }
                     | Event::KeyDown {
                         keycode: Some(Keycode::Escape),
@@ -80,50 +289,64 @@ impl Renderer {
                         self.exited = true;
                         break;
                     }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::BACKSPACE),
+                        ..
+                    } => self.rewinding = true,
+                    Event::KeyUp {
+                        keycode: Some(Keycode::BACKSPACE),
+                        ..
+                    } => self.rewinding = false,
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F12),
+                        ..
+                    } => self.debug_requested = true,
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F11),
+                        repeat: false,
+                        ..
+                    } => toggle_debug_window = true,
                     Event::KeyDown {
                         keycode: Some(key), ..
-                    } => match key {
-                        Keycode::Z => new_input.A = true,
-                        Keycode::X => new_input.B = true,
-                        Keycode::SPACE => new_input.Start = true,
-                        Keycode::V => new_input.Select = true,
-                        Keycode::UP => new_input.Up = true,
-                        Keycode::DOWN => new_input.Down = true,
-                        Keycode::LEFT => new_input.Left = true,
-                        Keycode::RIGHT => new_input.Right = true,
-
-                        _ => {}
-                    },
+                    } => {
+                        for (keymap, input) in self.keymaps.iter().zip(new_inputs.iter_mut()) {
+                            keymap.apply(key, true, input);
+                        }
+                    }
                     Event::KeyUp {
                         keycode: Some(key), ..
-                    } => match key {
-                        Keycode::Z => new_input.A = false,
-                        Keycode::X => new_input.B = false,
-                        Keycode::SPACE => new_input.Start = false,
-                        Keycode::V => new_input.Select = false,
-                        Keycode::UP => new_input.Up = false,
-                        Keycode::DOWN => new_input.Down = false,
-                        Keycode::LEFT => new_input.Left = false,
-                        Keycode::RIGHT => new_input.Right = false,
-                        _ => {}
-                    },
+                    } => {
+                        for (keymap, input) in self.keymaps.iter().zip(new_inputs.iter_mut()) {
+                            keymap.apply(key, false, input);
+                        }
+                    }
                     _ => {}
                 }
             }
         }
-        if new_input == self.last_input {
-            None
-        } else {
-            self.last_input = new_input;
-            Some(&self.last_input)
+        if toggle_debug_window {
+            self.toggle_debug_window();
         }
+        if toggle_memory_window {
+            self.toggle_memory_window();
+        }
+        if toggle_cheat_window {
+            self.toggle_cheat_window();
+        }
+        let changed = new_inputs
+            .iter()
+            .zip(self.last_inputs.iter())
+            .map(|(new_input, last_input)| (new_input != last_input).then(|| new_input.clone()))
+            .collect();
+        self.last_inputs = new_inputs;
+        changed
 
         // ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 30));
 
         // The rest of the game loop goes here...
     }
 
-    fn draw_main(&mut self, pixels: Vec<Color>) {
+    fn draw_main(&mut self, pixels: &FrameBuffer, x_offset: i32) {
         // for (x, y, pixel) in self.imgbuf.enumerate_pixels_mut() {
         //     println!("x is {x}");
         //     let color = pixels[(y + ((x) * 160)) as usize];
@@ -134,54 +357,441 @@ impl Renderer {
             for row in 0..144 {
                 for col in 0..160 {
                     // imgbuf.
-                    let color = pixels[col as usize + (row * 160) as usize];
+                    let color = sdl_color(pixels.get(col as usize, row as usize));
                     // let pixel = imgbuf.get_pixel_mut(col, row);
                     // *pixel = image::Rgb([color.r, color.g, color.b]);
                     canvas.set_draw_color(color);
-                    _ = canvas.draw_point(Point::new(col, row));
+                    _ = canvas.draw_point(Point::new(x_offset + col, row));
                     // canvas.
                 }
             }
         }
     }
-    //
-    fn update_debug_window(&mut self, debug_vram: [u8; 8192]) {
-        let (mut x_draw, mut y_draw, mut tile_index) = (160, 0, 0);
-        //384 tile. 16x24
 
-        for y in 0..24 {
-            for x in 0..16 {
-                self.display_debug_tile(debug_vram, tile_index, x_draw + x, y_draw + y);
-                x_draw += 8;
-                tile_index += 1;
+    pub fn toggle_debug_window(&mut self) {
+        if self.debug_canvas.take().is_some() {
+            return;
+        }
+        let Some(sdl_context) = &self.sdl_context else {
+            return;
+        };
+        let canvas = sdl_context
+            .video()
+            .and_then(|video| {
+                video
+                    .window("Rustboy VRAM", DEBUG_WIDTH as u32, DEBUG_HEIGHT as u32)
+                    .build()
+                    .map_err(|e| e.to_string())
+            })
+            .and_then(|window| window.into_canvas().build().map_err(|e| e.to_string()));
+        match canvas {
+            Ok(canvas) => self.debug_canvas = Some(canvas),
+            Err(err) => println!("No VRAM viewer: {}", err),
+        }
+    }
+
+    pub fn toggle_memory_window(&mut self) {
+        if self.memory_viewer.take().is_some() {
+            return;
+        }
+        let Some(sdl_context) = &self.sdl_context else {
+            return;
+        };
+        match MemoryViewer::open(sdl_context) {
+            Ok(viewer) => self.memory_viewer = Some(viewer),
+            Err(err) => println!("No memory viewer: {}", err),
+        }
+    }
+
+    // applies the memory viewer's edits and redraws it, if it is open
+    pub fn update_memory_window(&mut self, bus: &mut Bus) {
+        if let Some(viewer) = &mut self.memory_viewer {
+            viewer.update(bus);
+        }
+    }
+
+    pub fn toggle_cheat_window(&mut self) {
+        if self.cheat_list.take().is_some() {
+            return;
+        }
+        let Some(sdl_context) = &self.sdl_context else {
+            return;
+        };
+        match CheatList::open(sdl_context) {
+            Ok(cheat_list) => self.cheat_list = Some(cheat_list),
+            Err(err) => println!("No cheat list: {}", err),
+        }
+    }
+
+    // applies the changes made in the cheat list and redraws it, if it is open
+    pub fn update_cheat_window(&mut self, gameboy: &mut GameBoy) {
+        if let Some(cheat_list) = &mut self.cheat_list {
+            cheat_list.update(gameboy);
+        }
+    }
+
+    // Redraws the VRAM viewer from the PPU as it is right now, if it is open
+    pub fn update_debug_window(&mut self, ppu: &PPU) {
+        let Some(canvas) = &mut self.debug_canvas else {
+            return;
+        };
+        let mut image = DebugImage::new(DEBUG_WIDTH, DEBUG_HEIGHT);
+        let lcd = &ppu.lcd;
+
+        // both maps with the screen on top of the one the BG uses, then the
+        // window's map with the part of it that is on screen
+        for (map, x) in [(0x9800, 0), (0x9C00, MAP_SIZE + GAP)] {
+            let shown = map == lcd.lcdc_bg_map_area();
+            let label = format!("BG {:04X}{}", map, if shown { " SCREEN" } else { "" });
+            image.text(x, 2, &label, LABEL_COLOR);
+            draw_map(&mut image, ppu, map, x, MAP_Y);
+            if shown {
+                let (x_offset, y_offset) = (lcd.scroll_x as usize, lcd.scroll_y as usize);
+                image.outline_wrapped(x, MAP_Y, x_offset, y_offset, 160, 144);
+            }
+        }
+        let window_x = 2 * (MAP_SIZE + GAP);
+        let window_map = lcd.lcdc_window_tile_map_area();
+        let label = format!(
+            "WINDOW {:04X}{}",
+            window_map,
+            if lcd.lcdc_window_enabled() {
+                ""
+            } else {
+                " OFF"
+            }
+        );
+        image.text(window_x, 2, &label, LABEL_COLOR);
+        draw_map(&mut image, ppu, window_map, window_x, MAP_Y);
+        // WX is the left edge plus 7
+        let width = 167usize.saturating_sub(lcd.win_x as usize).min(160);
+        let height = 144usize.saturating_sub(lcd.win_y as usize);
+        if lcd.lcdc_window_enabled() && width > 0 && height > 0 {
+            image.outline_wrapped(window_x, MAP_Y, 0, 0, width, height);
+        }
+
+        // every tile, the second bank only exists on a CGB
+        image.text(0, ROW_Y, "TILES 8000-97FF", LABEL_COLOR);
+        let banks = if ppu.cgb_mode { 2 } else { 1 };
+        for bank in 0..banks {
+            if bank == 1 {
+                image.text(MAP_SIZE + GAP, ROW_Y, "BANK 1", LABEL_COLOR);
+            }
+            for tile in 0..384 {
+                let x = bank * (MAP_SIZE + GAP) + (tile % 16) * 16;
+                let y = ROW_Y + 12 + (tile / 16) * 16;
+                let data = tile_data(&ppu.vram, bank, tile);
+                display_debug_tile(&mut image, data, x, y, 2, &COLORS);
+            }
+        }
+
+        let mut y = ROW_Y;
+        image.text(window_x, y, "PALETTES", LABEL_COLOR);
+        for (name, value, colors) in [
+            ("BGP", lcd.bg_pallete, &lcd.bg_colors),
+            ("OBP0", lcd.obj_pallete[0], &lcd.obj0_colors),
+            ("OBP1", lcd.obj_pallete[1], &lcd.obj1_colors),
+        ] {
+            y += 20;
+            image.text(
+                window_x,
+                y + 3,
+                &format!("{} {:02X}", name, value),
+                TEXT_COLOR,
+            );
+            for (index, color) in colors.iter().enumerate() {
+                image.fill(window_x + 72 + index * 20, y, 16, 16, *color);
+            }
+        }
+        y += 32;
+        for line in [
+            format!(
+                "LCDC {:02X} STAT {:02X} LY {:02X}",
+                lcd.lcdc, lcd.lcds, lcd.ly
+            ),
+            format!("SCX {:02X} SCY {:02X}", lcd.scroll_x, lcd.scroll_y),
+            format!("WX {:02X} WY {:02X}", lcd.win_x, lcd.win_y),
+        ] {
+            image.text(window_x, y, &line, TEXT_COLOR);
+            y += 14;
+        }
+
+        // index, position, tile, palette and the X flip, Y flip and behind BG flags
+        image.text(OAM_X, 2, "OAM", LABEL_COLOR);
+        let tall = lcd.lcdc_obj_double_size();
+        for (index, entry) in ppu.oam_ram.iter().enumerate() {
+            let y = MAP_Y + index * 16;
+            let colors = if ppu.cgb_mode {
+                [0, 1, 2, 3].map(|color| lcd.cgb_obj_color(entry.cgb_palette(), color))
+            } else if entry.palette() == 0 {
+                lcd.obj0_colors
+            } else {
+                lcd.obj1_colors
+            };
+            let bank = if ppu.cgb_mode { entry.cgb_bank() } else { 0 };
+            // the top half of an 8x16 object, its tile index ignores bit 0
+            let tile = if tall {
+                entry.tile_idx & 0xFE
+            } else {
+                entry.tile_idx
+            };
+            let data = tile_data(&ppu.vram, bank, tile as usize);
+            display_debug_tile(&mut image, data, OAM_X, y, 2, &colors);
+            let palette = if ppu.cgb_mode {
+                format!("C{} V{}", entry.cgb_palette(), entry.cgb_bank())
+            } else {
+                format!("P{}", entry.palette())
+            };
+            let flag = |set: bool, name: &'static str| if set { name } else { "-" };
+            let line = format!(
+                "{:02} Y{:02X} X{:02X} T{:02X} {} {}{}{}",
+                index,
+                entry.y,
+                entry.x,
+                entry.tile_idx,
+                palette,
+                flag(entry.x_flipped(), "X"),
+                flag(entry.y_flipped(), "Y"),
+                flag(entry.draw_under_bg(), "B")
+            );
+            image.text(OAM_X + 20, y + 3, &line, TEXT_COLOR);
+        }
+
+        if let Err(err) = image.present(canvas) {
+            println!("VRAM viewer: {}", err);
+        }
+    }
+}
+
+// VRAM viewer layout, the maps go along the top and the OAM list down the right
+static DEBUG_WIDTH: usize = 1016;
+static DEBUG_HEIGHT: usize = 684;
+static MAP_SIZE: usize = 256;
+static GAP: usize = 8;
+static MAP_Y: usize = 14;
+static ROW_Y: usize = 280;
+static OAM_X: usize = 3 * (MAP_SIZE + GAP);
+pub(crate) static BACKGROUND: ppu::Color = ppu::Color::rgb(0x30, 0x30, 0x30);
+pub(crate) static TEXT_COLOR: ppu::Color = ppu::Color::rgb(0xE0, 0xE0, 0xE0);
+pub(crate) static LABEL_COLOR: ppu::Color = ppu::Color::rgb(0xF0, 0xC0, 0x40);
+static VIEWPORT_COLOR: ppu::Color = ppu::Color::rgb(0xFF, 0x30, 0x30);
+
+// RGB24 pixels for the viewer windows, uploaded in one go once everything is drawn
+pub(crate) struct DebugImage {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl DebugImage {
+    pub(crate) fn new(width: usize, height: usize) -> Self {
+        let mut image = DebugImage {
+            width,
+            height,
+            pixels: vec![0; width * height * 3],
+        };
+        image.fill(0, 0, width, height, BACKGROUND);
+        image
+    }
+
+    fn set(&mut self, x: usize, y: usize, color: ppu::Color) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let offset = (y * self.width + x) * 3;
+        self.pixels[offset..offset + 3].copy_from_slice(&[color.r, color.g, color.b]);
+    }
+
+    pub(crate) fn fill(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        color: ppu::Color,
+    ) {
+        for row in y..y + height {
+            for col in x..x + width {
+                self.set(col, row, color);
             }
-            y_draw += 8;
-            x_draw = 160;
         }
     }
 
-    fn display_debug_tile(&mut self, debug_vram: [u8; 8192], tile_index: usize, x: i32, y: i32) {
-        if let Some(canvas) = &mut self.canvas {
-            for row in 0..16 {
-                let b1 = debug_vram[(tile_index * 16) + row];
-                let b2 = debug_vram[(tile_index * 16) + row + 1];
-                let mut bit = 7i32;
-                while bit >= 0 {
-                    let hi = (b1 >> bit & 1) << 1;
-                    let low = b2 >> bit & 1;
-                    let col = COLORS[(hi | low) as usize];
-                    // let r = Rect::new(
-                    //     x + (7 - bit),
-                    //     y + (row as i32 / 2),
-                    //     SCALE as u32,
-                    //     SCALE as u32,
-                    // );
-                    canvas.set_draw_color(col);
-                    _ = canvas.draw_point(Point::new(x + (7 - bit), y + (row as i32 / 2)));
-                    // _ = canvas.fill_rect(r);
-                    bit -= 1;
+    // A width x height frame at (x_offset, y_offset) of the map drawn at (x, y),
+    // wrapping around its edges like scrolling does
+    fn outline_wrapped(
+        &mut self,
+        x: usize,
+        y: usize,
+        x_offset: usize,
+        y_offset: usize,
+        width: usize,
+        height: usize,
+    ) {
+        let mut dot = |col: usize, row: usize| {
+            self.set(
+                x + (x_offset + col) % MAP_SIZE,
+                y + (y_offset + row) % MAP_SIZE,
+                VIEWPORT_COLOR,
+            )
+        };
+        for col in 0..width {
+            dot(col, 0);
+            dot(col, height - 1);
+        }
+        for row in 0..height {
+            dot(0, row);
+            dot(width - 1, row);
+        }
+    }
+
+    // 3x5 glyphs at double size, 8 pixels apart
+    pub(crate) fn text(&mut self, x: usize, y: usize, text: &str, color: ppu::Color) {
+        for (index, c) in text.chars().enumerate() {
+            let glyph = glyph(c.to_ascii_uppercase());
+            for bit in 0..15 {
+                if glyph >> (14 - bit) & 1 > 0 {
+                    let col = x + index * 8 + (bit % 3) * 2;
+                    self.fill(col, y + (bit / 3) * 2, 2, 2, color);
                 }
             }
         }
     }
+
+    pub(crate) fn present(
+        &self,
+        canvas: &mut sdl2::render::Canvas<sdl2::video::Window>,
+    ) -> Result<(), String> {
+        let creator = canvas.texture_creator();
+        let mut texture = creator
+            .create_texture_streaming(
+                PixelFormatEnum::RGB24,
+                self.width as u32,
+                self.height as u32,
+            )
+            .map_err(|e| e.to_string())?;
+        texture
+            .update(None, &self.pixels, self.width * 3)
+            .map_err(|e| e.to_string())?;
+        canvas.copy(&texture, None, None)?;
+        canvas.present();
+        Ok(())
+    }
+}
+
+// the 16 bytes of tile `tile_index` (0-383) in a VRAM bank
+fn tile_data(vram: &[u8], bank: usize, tile_index: usize) -> &[u8] {
+    let start = bank * 0x2000 + tile_index * 16;
+    &vram[start..start + 16]
+}
+
+// Every row of a tile is two bytes, the first has the low bit of each
+// pixel's color and the second the high bit
+fn display_debug_tile(
+    image: &mut DebugImage,
+    tile: &[u8],
+    x: usize,
+    y: usize,
+    scale: usize,
+    colors: &[ppu::Color; 4],
+) {
+    for row in 0..8 {
+        let low = tile[row * 2];
+        let high = tile[row * 2 + 1];
+        for col in 0..8 {
+            let bit = 7 - col;
+            let color = ((high >> bit) & 1) << 1 | ((low >> bit) & 1);
+            image.fill(
+                x + col * scale,
+                y + row * scale,
+                scale,
+                scale,
+                colors[color as usize],
+            );
+        }
+    }
+}
+
+// A 32x32 tile map with the tile data area LCDC selects, and the CGB
+// attributes from bank 1 when there are any
+fn draw_map(image: &mut DebugImage, ppu: &PPU, map: usize, x: usize, y: usize) {
+    let lcd = &ppu.lcd;
+    for entry in 0..32 * 32 {
+        let tile = ppu.vram_read_bank(0, map + entry);
+        // 8800 addressing counts from 9000 with a signed index
+        let tile_index = if lcd.lcdc_bg_data_area() == 0x8000 {
+            tile as usize
+        } else {
+            tile.wrapping_add(128) as usize + 128
+        };
+        let (bank, colors) = if ppu.cgb_mode {
+            let attributes = ppu.vram_read_bank(1, map + entry);
+            let colors = [0, 1, 2, 3].map(|color| lcd.cgb_bg_color(attributes & 0x07, color));
+            ((attributes >> 3 & 1) as usize, colors)
+        } else {
+            (0, lcd.bg_colors)
+        };
+        let (col, row) = (entry % 32, entry / 32);
+        display_debug_tile(
+            image,
+            tile_data(&ppu.vram, bank, tile_index),
+            x + col * 8,
+            y + row * 8,
+            1,
+            &colors,
+        );
+    }
+}
+
+// 3x5 bitmaps, 3 bits per row from the top, for what the viewers write
+fn glyph(c: char) -> u16 {
+    match c {
+        '0' => 0b111_101_101_101_111,
+        '1' => 0b010_110_010_010_111,
+        '2' => 0b111_001_111_100_111,
+        '3' => 0b111_001_111_001_111,
+        '4' => 0b101_101_111_001_001,
+        '5' => 0b111_100_111_001_111,
+        '6' => 0b111_100_111_101_111,
+        '7' => 0b111_001_001_001_001,
+        '8' => 0b111_101_111_101_111,
+        '9' => 0b111_101_111_001_111,
+        'A' => 0b010_101_111_101_101,
+        'B' => 0b110_101_110_101_110,
+        'C' => 0b011_100_100_100_011,
+        'D' => 0b110_101_101_101_110,
+        'E' => 0b111_100_110_100_111,
+        'F' => 0b111_100_110_100_100,
+        'G' => 0b011_100_101_101_011,
+        'H' => 0b101_101_111_101_101,
+        'I' => 0b111_010_010_010_111,
+        'J' => 0b001_001_001_101_010,
+        'K' => 0b101_101_110_101_101,
+        'L' => 0b100_100_100_100_111,
+        'M' => 0b101_111_111_101_101,
+        'N' => 0b110_101_101_101_101,
+        'O' => 0b010_101_101_101_010,
+        'P' => 0b110_101_110_100_100,
+        'Q' => 0b010_101_101_110_011,
+        'R' => 0b110_101_110_101_101,
+        'S' => 0b011_100_010_001_110,
+        'T' => 0b111_010_010_010_010,
+        'U' => 0b101_101_101_101_111,
+        'V' => 0b101_101_101_101_010,
+        'W' => 0b101_101_111_111_101,
+        'X' => 0b101_101_010_101_101,
+        'Y' => 0b101_101_010_010_010,
+        'Z' => 0b111_001_010_100_111,
+        ':' => 0b000_010_000_010_000,
+        '-' => 0b000_000_111_000_000,
+        '+' => 0b000_010_111_010_000,
+        '/' => 0b001_001_010_100_100,
+        '.' => 0b000_000_000_000_010,
+        '=' => 0b000_111_000_111_000,
+        '[' => 0b011_010_010_010_011,
+        ']' => 0b110_010_010_010_110,
+        '\'' => 0b010_010_000_000_000,
+        '_' => 0b000_000_000_000_111,
+        _ => 0,
+    }
 }
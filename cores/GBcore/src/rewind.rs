@@ -0,0 +1,243 @@
+use std::collections::VecDeque;
+
+use crate::GameBoy;
+
+// Ring buffer of save states for stepping back in time. Only the newest
+// snapshot is kept whole, every older one is stored as a compressed delta
+// that turns its successor back into it. When the memory budget runs out
+// the oldest deltas are dropped first.
+pub struct Rewind {
+    interval: u32,
+    budget: usize,
+    frame_counter: u32,
+    newest: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>,
+    deltas_size: usize,
+}
+
+impl Rewind {
+    // capture a snapshot every `interval` frames, using at most `budget` bytes
+    pub fn new(interval: u32, budget: usize) -> Self {
+        Rewind {
+            interval: interval.max(1),
+            budget,
+            frame_counter: 0,
+            newest: None,
+            deltas: VecDeque::new(),
+            deltas_size: 0,
+        }
+    }
+
+    // call once per emulated frame
+    pub fn on_frame(&mut self, gameboy: &GameBoy) {
+        self.frame_counter += 1;
+        if self.frame_counter >= self.interval {
+            self.frame_counter = 0;
+            self.push(gameboy.save_state());
+        }
+    }
+
+    pub fn push(&mut self, state: Vec<u8>) {
+        if let Some(previous) = self.newest.take() {
+            let delta = encode_delta(&previous, &state);
+            self.deltas_size += delta.len();
+            self.deltas.push_back(delta);
+        }
+        self.newest = Some(state);
+
+        while self.memory_used() > self.budget {
+            match self.deltas.pop_front() {
+                Some(delta) => self.deltas_size -= delta.len(),
+                None => break,
+            }
+        }
+    }
+
+    // takes the newest snapshot out of the buffer
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let newest = self.newest.take()?;
+        if let Some(delta) = self.deltas.pop_back() {
+            self.deltas_size -= delta.len();
+            self.newest = Some(decode_delta(&newest, &delta));
+        }
+        Some(newest)
+    }
+
+    // Steps the emulator one snapshot back, Ok(false) when the buffer is
+    // exhausted. A snapshot that doesn't load empties the buffer.
+    pub fn rewind(&mut self, gameboy: &mut GameBoy) -> Result<bool, String> {
+        self.frame_counter = 0;
+        let Some(state) = self.pop() else {
+            return Ok(false);
+        };
+        if let Err(err) = gameboy.load_state(&state) {
+            self.clear();
+            return Err(format!("Rewind failed: {}", err));
+        }
+        Ok(true)
+    }
+
+    pub fn clear(&mut self) {
+        self.newest = None;
+        self.deltas.clear();
+        self.deltas_size = 0;
+        self.frame_counter = 0;
+    }
+
+    // number of snapshots that can be restored
+    pub fn len(&self) -> usize {
+        match self.newest {
+            Some(_) => self.deltas.len() + 1,
+            None => 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.newest.is_none()
+    }
+
+    pub fn memory_used(&self) -> usize {
+        self.deltas_size + self.newest.as_ref().map_or(0, |state| state.len())
+    }
+}
+
+// The delta is `old XOR new` (missing bytes count as zero), stored as
+// alternating runs: [zero run length][literal length][literal bytes]...
+fn encode_delta(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    delta.extend_from_slice(&(old.len() as u32).to_le_bytes());
+
+    let xor_at = |i: usize| old[i] ^ new.get(i).copied().unwrap_or(0);
+    let mut i = 0;
+    while i < old.len() {
+        let zeros_start = i;
+        while i < old.len() && xor_at(i) == 0 {
+            i += 1;
+        }
+        let literal_start = i;
+        // a single unchanged byte inside a change is cheaper to keep as a literal
+        while i < old.len() && (xor_at(i) != 0 || (i + 1 < old.len() && xor_at(i + 1) != 0)) {
+            i += 1;
+        }
+        write_varint(&mut delta, literal_start - zeros_start);
+        write_varint(&mut delta, i - literal_start);
+        for j in literal_start..i {
+            delta.push(xor_at(j));
+        }
+    }
+    delta
+}
+
+fn decode_delta(new: &[u8], delta: &[u8]) -> Vec<u8> {
+    let old_len = u32::from_le_bytes([delta[0], delta[1], delta[2], delta[3]]) as usize;
+    let mut old: Vec<u8> = (0..old_len)
+        .map(|i| new.get(i).copied().unwrap_or(0))
+        .collect();
+
+    let mut position = 4;
+    let mut i = 0;
+    while position < delta.len() {
+        i += read_varint(delta, &mut position);
+        let literal_len = read_varint(delta, &mut position);
+        for _ in 0..literal_len {
+            old[i] ^= delta[position];
+            position += 1;
+            i += 1;
+        }
+    }
+    old
+}
+
+fn write_varint(data: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        data.push((value as u8 & 0x7F) | 0x80);
+        value >>= 7;
+    }
+    data.push(value as u8);
+}
+
+fn read_varint(data: &[u8], position: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*position];
+        *position += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a fake save state: mostly the same from frame to frame, like the real ones
+    fn state(frame: u32) -> Vec<u8> {
+        let mut state: Vec<u8> = (0..4096).map(|i| (i % 251) as u8).collect();
+        state[..4].copy_from_slice(&frame.to_le_bytes());
+        state[1000 + frame as usize % 100] ^= 0xFF;
+        state
+    }
+
+    #[test]
+    fn delta_round_trip() {
+        let old = state(1);
+        let new = state(2);
+        let delta = encode_delta(&old, &new);
+        assert!(delta.len() < 32);
+        assert_eq!(decode_delta(&new, &delta), old);
+        assert_eq!(decode_delta(&old, &encode_delta(&old, &old)), old);
+    }
+
+    #[test]
+    fn delta_round_trip_different_lengths() {
+        let old = state(1);
+        let mut longer = state(2);
+        longer.extend_from_slice(&[0xAB; 300]);
+        assert_eq!(decode_delta(&longer, &encode_delta(&old, &longer)), old);
+
+        let shorter = &state(2)[..3000];
+        assert_eq!(decode_delta(shorter, &encode_delta(&old, shorter)), old);
+    }
+
+    #[test]
+    fn evicts_the_oldest() {
+        let mut rewind = Rewind::new(1, 4096 + 20 * 16);
+        for frame in 0..100 {
+            rewind.push(state(frame));
+            assert!(rewind.memory_used() <= 4096 + 20 * 16);
+        }
+        let kept = rewind.len() as u32;
+        assert!(kept > 1 && kept < 100);
+
+        for frame in (100 - kept..100).rev() {
+            assert_eq!(rewind.pop(), Some(state(frame)));
+        }
+        assert!(rewind.is_empty());
+        assert_eq!(rewind.pop(), None);
+    }
+
+    #[test]
+    fn rewinds_to_the_right_frame() {
+        let mut gameboy = GameBoy::test_rom("Tetris.gb");
+        let mut states = Vec::new();
+        // room for the newest snapshot and some of the deltas
+        let mut rewind = Rewind::new(1, gameboy.save_state().len() + 8192);
+        for _ in 0..120 {
+            gameboy.run_test_frames(1);
+            rewind.on_frame(&gameboy);
+            states.push(gameboy.save_state());
+        }
+        let kept = rewind.len();
+        assert!(kept > 1 && kept < 120, "{} kept", kept);
+
+        for steps in 1..=kept {
+            assert_eq!(rewind.rewind(&mut gameboy), Ok(true));
+            assert!(gameboy.save_state() == states[120 - steps]);
+        }
+        assert_eq!(rewind.rewind(&mut gameboy), Ok(false));
+    }
+}
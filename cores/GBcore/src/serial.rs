@@ -0,0 +1,207 @@
+use crate::state::{SaveState, StateReader, StateWriter};
+
+// T-cycles per bit on the internal clock: 8192Hz, or 262144Hz with the CGB fast clock.
+// Both follow the CPU clock, so double speed doubles the transfer rate as well
+static NORMAL_BIT_CYCLES: u32 = 512;
+static FAST_BIT_CYCLES: u32 = 16;
+// bytes kept around when nobody drains the output
+static OUTPUT_LIMIT: usize = 0x10000;
+
+// Whatever sits on the other end of the link cable. Transfers are handled a byte
+// at a time, which is all a peer can observe anyway.
+pub trait LinkDevice {
+    // We drive the clock: `data` has been shifted out, the return value is what
+    // the device shifted back in. An unplugged cable reads all ones.
+    fn exchange(&mut self, data: u8) -> u8;
+
+    // The device drives the clock. Polled while we wait on the external clock with
+    // `data` in SB, returns the byte it shifted in once it has clocked a full transfer.
+    fn clock_in(&mut self, data: u8) -> Option<u8> {
+        let _ = data;
+        None
+    }
+
+    // Emulated time passing, in T-cycles at the normal 4MHz clock. Called before
+    // every other method, so devices that sync with a remote peer can hold the
+    // machine back here.
+    fn advance(&mut self, elapsed: u32) {
+        let _ = elapsed;
+    }
+
+    // why a device that went away isn't there anymore
+    fn error(&self) -> Option<&str> {
+        None
+    }
+}
+
+// Nothing plugged in
+pub struct Disconnected;
+
+impl LinkDevice for Disconnected {
+    fn exchange(&mut self, _data: u8) -> u8 {
+        0xFF
+    }
+}
+
+pub struct Serial {
+    pub sb: u8, // FF01 - Serial transfer data
+    pub sc: u8, // FF02 - Serial transfer control
+    pub cgb_mode: bool,
+    outgoing: u8,
+    bits_left: u8,
+    cycles: u32,
+    pub device: Box<dyn LinkDevice>,
+    // every byte that went out, test ROMs report their results this way
+    output: Vec<u8>,
+    pub on_output: Option<Box<dyn FnMut(u8)>>,
+}
+
+impl Default for Serial {
+    fn default() -> Self {
+        Serial::new()
+    }
+}
+
+impl Serial {
+    pub fn new() -> Self {
+        Serial {
+            sb: 0,
+            sc: 0,
+            cgb_mode: false,
+            outgoing: 0,
+            bits_left: 0,
+            cycles: 0,
+            device: Box::new(Disconnected),
+            output: Vec::new(),
+            on_output: None,
+        }
+    }
+
+    pub fn read(&self, address: usize) -> u8 {
+        match address {
+            0xFF01 => self.sb,
+            // the clock speed bit only exists on CGB
+            0xFF02 if self.cgb_mode => self.sc | 0x7C,
+            0xFF02 => self.sc | 0x7E,
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, address: usize, value: u8) {
+        match address {
+            0xFF01 => self.sb = value,
+            0xFF02 => {
+                self.sc = value;
+                if self.transferring() {
+                    self.outgoing = self.sb;
+                    self.bits_left = 8;
+                    self.cycles = 0;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Like write, but the start bit stays as it is: a transfer can't be
+    // started or stopped without its side effects
+    pub fn poke(&mut self, address: usize, value: u8) {
+        match address {
+            0xFF01 => self.sb = value,
+            0xFF02 => self.sc = (value & 0x7F) | (self.sc & 0x80),
+            _ => {}
+        }
+    }
+
+    fn transferring(&self) -> bool {
+        self.sc & 0x80 > 0
+    }
+
+    fn internal_clock(&self) -> bool {
+        self.sc & 0x01 > 0
+    }
+
+    fn bit_cycles(&self) -> u32 {
+        if self.cgb_mode && self.sc & 0x02 > 0 {
+            FAST_BIT_CYCLES
+        } else {
+            NORMAL_BIT_CYCLES
+        }
+    }
+
+    // Advances by `cycles` T-cycles on the CPU clock, which are `elapsed` T-cycles at
+    // normal speed. Returns true when a transfer finished and the serial interrupt
+    // has to be requested.
+    pub fn tick(&mut self, cycles: u32, elapsed: u32) -> bool {
+        self.device.advance(elapsed);
+        if !self.transferring() {
+            return false;
+        }
+        if !self.internal_clock() {
+            return match self.device.clock_in(self.sb) {
+                Some(data) => {
+                    self.sent(self.sb);
+                    self.finish(data);
+                    true
+                }
+                None => false,
+            };
+        }
+
+        self.cycles += cycles;
+        let bit_cycles = self.bit_cycles();
+        while self.cycles >= bit_cycles {
+            self.cycles -= bit_cycles;
+            self.bits_left -= 1;
+            if self.bits_left == 0 {
+                let data = self.device.exchange(self.outgoing);
+                self.sent(self.outgoing);
+                self.finish(data);
+                return true;
+            }
+            // the incoming bits are only known once the peer has seen the whole byte
+            self.sb = (self.sb << 1) | 1;
+        }
+        false
+    }
+
+    fn sent(&mut self, data: u8) {
+        if self.output.len() >= OUTPUT_LIMIT {
+            self.output.drain(..OUTPUT_LIMIT / 2);
+        }
+        self.output.push(data);
+        if let Some(on_output) = &mut self.on_output {
+            on_output(data);
+        }
+    }
+
+    pub fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.output)
+    }
+
+    fn finish(&mut self, data: u8) {
+        self.sb = data;
+        self.sc &= 0x7F;
+        self.bits_left = 0;
+        self.cycles = 0;
+    }
+}
+
+// the link device is part of the setup, not of the machine, and stays connected
+impl SaveState for Serial {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.sb);
+        writer.write_u8(self.sc);
+        writer.write_u8(self.outgoing);
+        writer.write_u8(self.bits_left);
+        writer.write_u32(self.cycles);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.sb = reader.read_u8()?;
+        self.sc = reader.read_u8()?;
+        self.outgoing = reader.read_u8()?;
+        self.bits_left = reader.read_u8()?;
+        self.cycles = reader.read_u32()?;
+        Ok(())
+    }
+}
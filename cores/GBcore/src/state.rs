@@ -0,0 +1,193 @@
+use crate::cartridge::cart_info::CartridgeInfo;
+
+pub static STATE_MAGIC: &[u8; 4] = b"RBST";
+pub static STATE_VERSION: u16 = 8;
+
+// Implemented by every component that holds emulation state. The layout is
+// plain little-endian fields in declaration order, guarded by the header below.
+pub trait SaveState {
+    fn save_state(&self, writer: &mut StateWriter);
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String>;
+}
+
+#[derive(Default)]
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        StateWriter { data: Vec::new() }
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+    pub fn write_bool(&mut self, value: bool) {
+        self.data.push(value as u8);
+    }
+    pub fn write_u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+    pub fn write_u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+    pub fn write_u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+    pub fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+    // fixed size data, the reader has to know the length
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+    // length prefixed data
+    pub fn write_vec(&mut self, bytes: &[u8]) {
+        self.write_u32(bytes.len() as u32);
+        self.data.extend_from_slice(bytes);
+    }
+    pub fn write_str(&mut self, value: &str) {
+        self.write_vec(value.as_bytes());
+    }
+    pub fn write_banks(&mut self, banks: &Option<Vec<Vec<u8>>>) {
+        match banks {
+            Some(banks) => {
+                self.write_bool(true);
+                self.write_u32(banks.len() as u32);
+                for bank in banks {
+                    self.write_vec(bank);
+                }
+            }
+            None => self.write_bool(false),
+        }
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        StateReader { data, position: 0 }
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        if self.position + count > self.data.len() {
+            return Err("Save state is truncated".to_string());
+        }
+        let slice = &self.data[self.position..self.position + count];
+        self.position += count;
+        Ok(slice)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+    pub fn read_bool(&mut self) -> Result<bool, String> {
+        Ok(self.read_u8()? != 0)
+    }
+    pub fn read_u16(&mut self) -> Result<u16, String> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+    pub fn read_u32(&mut self) -> Result<u32, String> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+    pub fn read_u64(&mut self) -> Result<u64, String> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+    pub fn read_usize(&mut self) -> Result<usize, String> {
+        Ok(self.read_u64()? as usize)
+    }
+    pub fn read_bytes(&mut self, into: &mut [u8]) -> Result<(), String> {
+        into.copy_from_slice(self.take(into.len())?);
+        Ok(())
+    }
+    pub fn read_vec(&mut self) -> Result<Vec<u8>, String> {
+        let len = self.read_u32()? as usize;
+        Ok(self.take(len)?.to_vec())
+    }
+    pub fn read_str(&mut self) -> Result<String, String> {
+        String::from_utf8(self.read_vec()?).map_err(|_| "Invalid string in save state".to_string())
+    }
+    // reads banks into already allocated memory, sizes have to match
+    pub fn read_banks(&mut self, banks: &mut Option<Vec<Vec<u8>>>) -> Result<(), String> {
+        let present = self.read_bool()?;
+        match banks {
+            Some(banks) if present => {
+                let count = self.read_u32()? as usize;
+                if count != banks.len() {
+                    return Err("Save state RAM bank count mismatch".to_string());
+                }
+                for bank in banks.iter_mut() {
+                    let data = self.read_vec()?;
+                    if data.len() != bank.len() {
+                        return Err("Save state RAM bank size mismatch".to_string());
+                    }
+                    bank.copy_from_slice(&data);
+                }
+                Ok(())
+            }
+            None if !present => Ok(()),
+            _ => Err("Save state RAM layout mismatch".to_string()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.position >= self.data.len()
+    }
+}
+
+pub fn write_header(writer: &mut StateWriter, info: &CartridgeInfo) {
+    writer.write_bytes(STATE_MAGIC);
+    writer.write_u16(STATE_VERSION);
+    writer.write_str(&info.title);
+    writer.write_u8(info.header_checksum);
+    writer.write_u16(info.global_checksum);
+}
+
+pub fn check_header(reader: &mut StateReader, info: &CartridgeInfo) -> Result<(), String> {
+    let mut magic = [0; 4];
+    reader
+        .read_bytes(&mut magic)
+        .map_err(|_| "Not a save state".to_string())?;
+    if &magic != STATE_MAGIC {
+        return Err("Not a save state".to_string());
+    }
+    let version = reader.read_u16()?;
+    if version != STATE_VERSION {
+        return Err(format!(
+            "Unsupported save state version {} (expected {})",
+            version, STATE_VERSION
+        ));
+    }
+    let title = reader.read_str()?;
+    let header_checksum = reader.read_u8()?;
+    let global_checksum = reader.read_u16()?;
+    if title != info.title
+        || header_checksum != info.header_checksum
+        || global_checksum != info.global_checksum
+    {
+        return Err(format!(
+            "Save state was made for \"{}\" ({:02X}/{:04X}), not \"{}\" ({:02X}/{:04X})",
+            title.trim_end_matches('\0'),
+            header_checksum,
+            global_checksum,
+            info.title.trim_end_matches('\0'),
+            info.header_checksum,
+            info.global_checksum
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,179 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+// Labels from a symbol file. RGBDS and no$gmb write `.sym` files with one
+// `bank:address label` per line, GBDK writes NoICE `.noi` files with
+// `DEF label 0xBBAAAA` lines that keep the bank above the address.
+#[derive(Debug, Default)]
+pub struct Symbols {
+    // every label at an address, with its bank
+    labels: BTreeMap<u16, Vec<(usize, String)>>,
+    names: HashMap<String, (usize, u16)>,
+}
+
+// where a memory region starts, a label never reaches past its region
+fn region(address: u16) -> u16 {
+    match address {
+        0..0x4000 => 0,
+        0x4000..0x8000 => 0x4000,
+        0x8000..0xA000 => 0x8000,
+        0xA000..0xC000 => 0xA000,
+        0xC000..0xD000 => 0xC000,
+        0xD000..0xFE00 => 0xD000,
+        0xFE00..0xFF80 => 0xFE00,
+        _ => 0xFF80,
+    }
+}
+
+fn parse_line(line: &str) -> Option<(usize, u16, String)> {
+    let line = line.split(';').next()?.trim();
+    let mut words = line.split_whitespace();
+    let first = words.next()?;
+    if first == "DEF" {
+        let name = words.next()?;
+        let value = words.next()?;
+        let value = u32::from_str_radix(value.trim_start_matches("0x"), 16).ok()?;
+        return Some(((value >> 16) as usize, value as u16, name.to_string()));
+    }
+    let (bank, address) = first.split_once(':')?;
+    let bank = usize::from_str_radix(bank, 16).ok()?;
+    let address = u16::from_str_radix(address, 16).ok()?;
+    Some((bank, address, words.next()?.to_string()))
+}
+
+impl Symbols {
+    pub fn parse(text: &str) -> Symbols {
+        let mut symbols = Symbols::default();
+        for (bank, address, name) in text.lines().filter_map(parse_line) {
+            symbols.add(bank, address, name);
+        }
+        symbols
+    }
+
+    pub fn load(path: &str) -> Result<Symbols, String> {
+        let text =
+            fs::read_to_string(path).map_err(|err| format!("Couldn't read {}: {}", path, err))?;
+        Ok(Symbols::parse(&text))
+    }
+
+    // game.sym or game.noi next to game.gb, the way the assemblers leave them
+    pub fn for_rom(rom_path: &str) -> Option<Symbols> {
+        ["sym", "noi"].iter().find_map(|extension| {
+            let path = Path::new(rom_path).with_extension(extension);
+            path.exists()
+                .then(|| Symbols::load(&path.to_string_lossy()).ok())
+                .flatten()
+        })
+    }
+
+    pub fn add(&mut self, bank: usize, address: u16, name: String) {
+        self.names.entry(name.clone()).or_insert((bank, address));
+        self.labels.entry(address).or_default().push((bank, name));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    // bank and address of a label
+    pub fn find(&self, name: &str) -> Option<(usize, u16)> {
+        self.names.get(name).copied()
+    }
+
+    // The label at `address`. `bank` is what is mapped there, when nobody
+    // knows a switchable ROM address only gets a label no other bank has.
+    // RAM labels are found whatever their bank.
+    pub fn label(&self, address: u16, bank: Option<usize>) -> Option<&str> {
+        let labels = self.labels.get(&address)?;
+        let found = match (address, bank) {
+            (0..0x4000, _) => labels.iter().find(|(bank, _)| *bank == 0),
+            (0x4000..0x8000, Some(mapped)) => labels.iter().find(|(bank, _)| *bank == mapped),
+            (0x4000..0x8000, None) => match labels.as_slice() {
+                [only] => Some(only),
+                _ => labels
+                    .iter()
+                    .all(|(bank, _)| *bank == labels[0].0)
+                    .then(|| &labels[0]),
+            },
+            _ => labels.first(),
+        };
+        found.map(|(_, name)| name.as_str())
+    }
+
+    // `Main` or `Main+$1A` for the closest label at or before `address`
+    pub fn describe(&self, address: u16, bank: Option<usize>) -> Option<String> {
+        let start = region(address);
+        self.labels
+            .range(start..=address)
+            .rev()
+            .find_map(|(label_address, _)| {
+                let name = self.label(*label_address, bank)?;
+                Some(match address - label_address {
+                    0 => name.to_string(),
+                    offset => format!("{}+${:X}", name, offset),
+                })
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rgbds() {
+        let symbols = Symbols::parse(
+            "; File generated by rgblink\n00:0150 Start\n01:4000 Level.init\n00:c000 wState\n",
+        );
+        assert_eq!(symbols.len(), 3);
+        assert_eq!(symbols.find("Start"), Some((0, 0x0150)));
+        assert_eq!(symbols.find("Level.init"), Some((1, 0x4000)));
+        assert_eq!(symbols.find("wState"), Some((0, 0xC000)));
+    }
+
+    #[test]
+    fn nocash() {
+        let symbols = Symbols::parse(";no$gmb format .sym file\n0000:0100 Boot\n0003:6A00 Music\n");
+        assert_eq!(symbols.find("Boot"), Some((0, 0x0100)));
+        assert_eq!(symbols.find("Music"), Some((3, 0x6A00)));
+    }
+
+    #[test]
+    fn noice() {
+        let symbols = Symbols::parse("DEF _main 0x200\nDEF _song 0x25C00\nDEF .area_end 0x0\n");
+        assert_eq!(symbols.find("_main"), Some((0, 0x0200)));
+        assert_eq!(symbols.find("_song"), Some((2, 0x5C00)));
+        assert_eq!(symbols.label(0x5C00, Some(2)), Some("_song"));
+    }
+
+    #[test]
+    fn banks() {
+        let symbols = Symbols::parse("00:0200 Home\n01:4000 One\n02:4000 Two\n02:4100 OnlyTwo\n");
+        // bank 0 is always mapped, whatever the switchable slot holds
+        assert_eq!(symbols.label(0x0200, None), Some("Home"));
+        assert_eq!(symbols.label(0x0200, Some(5)), Some("Home"));
+
+        assert_eq!(symbols.label(0x4000, Some(1)), Some("One"));
+        assert_eq!(symbols.label(0x4000, Some(2)), Some("Two"));
+        assert_eq!(symbols.label(0x4000, Some(3)), None);
+        // two banks have a label there, no telling which one it is
+        assert_eq!(symbols.label(0x4000, None), None);
+        assert_eq!(symbols.label(0x4100, None), Some("OnlyTwo"));
+
+        assert_eq!(
+            symbols.describe(0x4010, Some(1)).as_deref(),
+            Some("One+$10")
+        );
+        assert_eq!(
+            symbols.describe(0x4110, Some(2)).as_deref(),
+            Some("OnlyTwo+$10")
+        );
+        // labels don't reach from bank 0 into the switchable slot
+        assert_eq!(symbols.describe(0x4010, Some(3)), None);
+    }
+}
@@ -1,4 +1,8 @@
-use crate::{interrupts::InterruptType, Bus, CPU};
+use crate::{
+    interrupts::InterruptType,
+    state::{SaveState, StateReader, StateWriter},
+    Bus, CPU,
+};
 use std::rc::Rc;
 
 pub struct Timer {
@@ -8,6 +12,8 @@ pub struct Timer {
     tac: u8,          // FF07 - Timer control
     div_cycles: u32,  // Internal counter for DIV
     tima_cycles: u32, // Internal counter for TIMA
+    apu_event: bool,  // DIV bit 4 fell, the APU frame sequencer has to step
+    pub double_speed: bool,
 
     pub request_interrupt: Option<Rc<dyn Fn(InterruptType)>>,
 }
@@ -21,6 +27,8 @@ impl Timer {
             tac: 0,
             div_cycles: 0,
             tima_cycles: 0,
+            apu_event: false,
+            double_speed: false,
             request_interrupt: None,
         }
     }
@@ -30,6 +38,12 @@ impl Timer {
         self.div = self.div.wrapping_add(1);
         let mut timer_update = false;
 
+        // DIV runs twice as fast in double speed mode, bit 5 keeps the sequencer at 512Hz
+        let apu_bit = if self.double_speed { 1 << 13 } else { 1 << 12 };
+        if (prev_div & apu_bit) > 0 && (self.div & apu_bit) == 0 {
+            self.apu_event = true;
+        }
+
         match self.tac & 0b11 {
             0b00 => timer_update = (prev_div & (1 << 9)) > 0 && ((self.div & (1 << 9)) == 0),
             0b01 => timer_update = (prev_div & (1 << 3)) > 0 && ((self.div & (1 << 3)) == 0),
@@ -62,7 +76,13 @@ impl Timer {
 
     pub fn write_byte(&mut self, address: usize, value: u8) {
         match address {
-            0xFF04 => self.div = 0,
+            0xFF04 => {
+                let apu_bit = if self.double_speed { 1 << 13 } else { 1 << 12 };
+                if (self.div & apu_bit) > 0 {
+                    self.apu_event = true;
+                }
+                self.div = 0
+            }
             0xFF05 => self.tima = value,
             0xFF06 => self.tma = value,
             0xFF07 => self.tac = value & 0x07,
@@ -71,6 +91,14 @@ impl Timer {
         }
     }
 
+    pub fn set_div(&mut self, div: u16) {
+        self.div = div;
+    }
+
+    pub fn take_apu_event(&mut self) -> bool {
+        std::mem::take(&mut self.apu_event)
+    }
+
     pub fn reset(&mut self) {
         self.div = 0;
         self.tima = 0;
@@ -80,3 +108,28 @@ impl Timer {
         self.tima_cycles = 0;
     }
 }
+
+impl SaveState for Timer {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u16(self.div);
+        writer.write_u8(self.tima);
+        writer.write_u8(self.tma);
+        writer.write_u8(self.tac);
+        writer.write_u32(self.div_cycles);
+        writer.write_u32(self.tima_cycles);
+        writer.write_bool(self.double_speed);
+        writer.write_bool(self.apu_event);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.div = reader.read_u16()?;
+        self.tima = reader.read_u8()?;
+        self.tma = reader.read_u8()?;
+        self.tac = reader.read_u8()?;
+        self.div_cycles = reader.read_u32()?;
+        self.tima_cycles = reader.read_u32()?;
+        self.double_speed = reader.read_bool()?;
+        self.apu_event = reader.read_bool()?;
+        Ok(())
+    }
+}
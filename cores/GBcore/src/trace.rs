@@ -0,0 +1,369 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+
+use std::rc::Rc;
+
+use crate::cpu::CPU;
+use crate::disasm;
+use crate::symbols::Symbols;
+
+#[derive(Debug, Clone, Default)]
+pub struct TraceOptions {
+    // stays quiet until the CPU first gets to this address
+    pub start_pc: Option<u16>,
+    // or until this many T-cycles have passed
+    pub start_cycle: Option<u64>,
+    // stops writing once the file reaches this many bytes
+    pub max_bytes: Option<u64>,
+    // ends every line with the label of PC, which Gameboy Doctor can't read
+    pub labels: bool,
+}
+
+// Writes one line per executed instruction in the Gameboy Doctor format:
+// A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02
+// Lines show the state before the instruction runs.
+pub struct Tracer {
+    writer: BufWriter<File>,
+    options: TraceOptions,
+    started: bool,
+    // the size limit was hit or writing failed
+    full: bool,
+    written: u64,
+    // the write that failed and ended the trace
+    error: Option<io::Error>,
+    pub symbols: Option<Rc<Symbols>>,
+}
+
+impl Tracer {
+    pub fn create(path: &str, options: TraceOptions) -> Result<Self, String> {
+        let file =
+            File::create(path).map_err(|err| format!("Couldn't create {}: {}", path, err))?;
+        Ok(Tracer {
+            writer: BufWriter::new(file),
+            started: options.start_pc.is_none() && options.start_cycle.is_none(),
+            options,
+            full: false,
+            written: 0,
+            error: None,
+            symbols: None,
+        })
+    }
+
+    pub fn trace(&mut self, cpu: &CPU) {
+        let regs = &cpu.regs;
+        if !self.started {
+            let at_pc = self.options.start_pc == Some(regs.pc);
+            let at_cycle = self
+                .options
+                .start_cycle
+                .is_some_and(|start_cycle| cpu.cycles >= start_cycle);
+            if !at_pc && !at_cycle {
+                return;
+            }
+            self.started = true;
+        }
+        if self.full {
+            return;
+        }
+        let pc = regs.pc as usize;
+        let mut line = format!(
+            "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+            regs.a,
+            regs.f,
+            regs.b,
+            regs.c,
+            regs.d,
+            regs.e,
+            regs.h,
+            regs.l,
+            regs.sp,
+            regs.pc,
+            cpu.bus.read8_unwatched(pc),
+            cpu.bus.read8_unwatched((pc + 1) & 0xFFFF),
+            cpu.bus.read8_unwatched((pc + 2) & 0xFFFF),
+            cpu.bus.read8_unwatched((pc + 3) & 0xFFFF),
+        );
+        if let Some(label) = self
+            .symbols
+            .as_ref()
+            .and_then(|symbols| symbols.describe(regs.pc, disasm::mapped_bank(&cpu.bus, regs.pc)))
+        {
+            line.push_str(&format!(" ; {}", label));
+        }
+        line.push('\n');
+        let written = self.written + line.len() as u64;
+        if self
+            .options
+            .max_bytes
+            .is_some_and(|max_bytes| written > max_bytes)
+        {
+            self.full = true;
+            _ = self.writer.flush();
+            return;
+        }
+        if let Err(err) = self.writer.write_all(line.as_bytes()) {
+            self.error = Some(err);
+            self.full = true;
+            return;
+        }
+        self.written = written;
+    }
+
+    pub fn last_error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    // reports a write that failed earlier as well
+    pub fn flush(&mut self) -> Result<(), String> {
+        if let Some(err) = &self.error {
+            return Err(format!("Trace error: {}", err));
+        }
+        self.writer
+            .flush()
+            .map_err(|err| format!("Trace error: {}", err))
+    }
+}
+
+// Where two traces stop agreeing
+pub struct Divergence {
+    // 1-based
+    pub line: usize,
+    // the lines both traces agree on right before the divergence
+    pub before: Vec<String>,
+    // None when that trace ended early
+    pub ours: Option<String>,
+    pub reference: Option<String>,
+    // the lines that follow in each trace
+    pub ours_after: Vec<String>,
+    pub reference_after: Vec<String>,
+    // name, our value, reference value
+    pub fields: Vec<(String, String, String)>,
+    // the instruction that ran last before the traces diverged
+    pub instruction: Option<String>,
+}
+
+// A:01 F:B0 ... into (name, value) pairs
+fn trace_fields(line: &str) -> Vec<(&str, &str)> {
+    line.split_whitespace()
+        .filter_map(|field| field.split_once(':'))
+        .collect()
+}
+
+// disassembles the instruction a trace line is about to run, from its PCMEM bytes
+fn trace_instruction(line: &str) -> Option<String> {
+    let fields = trace_fields(line);
+    let pc = fields.iter().find(|(name, _)| *name == "PC")?.1;
+    let memory = fields.iter().find(|(name, _)| *name == "PCMEM")?.1;
+    let bytes: Vec<u8> = memory
+        .split(',')
+        .filter_map(|byte| u8::from_str_radix(byte, 16).ok())
+        .collect();
+    if bytes.is_empty() {
+        return None;
+    }
+    let address = u16::from_str_radix(pc, 16).ok()?;
+    let (text, _) = disasm::decode(&bytes, address);
+    Some(format!("{} {} ({})", pc, text, memory))
+}
+
+// Compares two traces line by line. Only fields present in both lines are
+// compared, so a reference without PCMEM or with extra fields still lines up.
+fn lines_match(ours: &str, reference: &str) -> bool {
+    differing_fields(ours, reference).is_empty()
+}
+
+fn differing_fields(ours: &str, reference: &str) -> Vec<(String, String, String)> {
+    let reference_fields = trace_fields(reference);
+    trace_fields(ours)
+        .into_iter()
+        .filter_map(|(name, value)| {
+            let (_, reference_value) = reference_fields.iter().find(|(n, _)| *n == name)?;
+            (!value.eq_ignore_ascii_case(reference_value)).then(|| {
+                (
+                    name.to_string(),
+                    value.to_string(),
+                    reference_value.to_string(),
+                )
+            })
+        })
+        .collect()
+}
+
+fn open_lines(path: &str) -> Result<impl Iterator<Item = String>, String> {
+    let file = File::open(path).map_err(|err| format!("Couldn't open {}: {}", path, err))?;
+    Ok(BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter(|line| !line.trim().is_empty()))
+}
+
+// Finds the first line where our trace and the reference disagree, with
+// `context` lines around it. None when they match all the way.
+pub fn diff_traces(
+    ours: &str,
+    reference: &str,
+    context: usize,
+) -> Result<Option<Divergence>, String> {
+    let mut ours = open_lines(ours)?;
+    let mut reference = open_lines(reference)?;
+    let mut before: VecDeque<String> = VecDeque::new();
+    let mut line = 0;
+    loop {
+        line += 1;
+        let (our_line, reference_line) = match (ours.next(), reference.next()) {
+            (None, None) => return Ok(None),
+            (Some(our_line), Some(reference_line)) => {
+                if lines_match(&our_line, &reference_line) {
+                    before.push_back(our_line);
+                    if before.len() > context {
+                        before.pop_front();
+                    }
+                    continue;
+                }
+                (Some(our_line), Some(reference_line))
+            }
+            lines => lines,
+        };
+
+        let fields = match (&our_line, &reference_line) {
+            (Some(our_line), Some(reference_line)) => differing_fields(our_line, reference_line),
+            _ => Vec::new(),
+        };
+        return Ok(Some(Divergence {
+            line,
+            instruction: before.back().and_then(|line| trace_instruction(line)),
+            before: before.into(),
+            ours: our_line,
+            reference: reference_line,
+            ours_after: ours.take(context).collect(),
+            reference_after: reference.take(context).collect(),
+            fields,
+        }));
+    }
+}
+
+impl Divergence {
+    pub fn report(&self) -> String {
+        let mut report = format!("First difference at line {}\n", self.line);
+        let first = self.line - self.before.len();
+        for (index, line) in self.before.iter().enumerate() {
+            report.push_str(&format!("  {: >8}  {}\n", first + index, line));
+        }
+        let missing = "<end of trace>".to_string();
+        report.push_str(&format!(
+            "> {: >8}  {}  (ours)\n",
+            self.line,
+            self.ours.as_ref().unwrap_or(&missing)
+        ));
+        report.push_str(&format!(
+            "> {: >8}  {}  (reference)\n",
+            self.line,
+            self.reference.as_ref().unwrap_or(&missing)
+        ));
+        for (name, ours, reference) in &self.fields {
+            report.push_str(&format!("  {} is {}, expected {}\n", name, ours, reference));
+        }
+        if let Some(instruction) = &self.instruction {
+            report.push_str(&format!("  after running {}\n", instruction));
+        }
+        for (label, lines) in [
+            ("ours", &self.ours_after),
+            ("reference", &self.reference_after),
+        ] {
+            if lines.is_empty() {
+                continue;
+            }
+            report.push_str(&format!("then in {}:\n", label));
+            for (index, line) in lines.iter().enumerate() {
+                report.push_str(&format!("  {: >8}  {}\n", self.line + 1 + index, line));
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn line(pc: u16, a: u8) -> String {
+        format!(
+            "A:{:02X} F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:{:04X} PCMEM:3C,00,00,00",
+            a, pc
+        )
+    }
+
+    // our trace and the reference, as files the way --trace leaves them
+    fn diff(name: &str, ours: &[String], reference: &[String]) -> Option<Divergence> {
+        let directory = std::env::temp_dir();
+        let ours_path = directory.join(format!("rustboy-{}-{}-ours.log", name, std::process::id()));
+        let reference_path = directory.join(format!(
+            "rustboy-{}-{}-reference.log",
+            name,
+            std::process::id()
+        ));
+        fs::write(&ours_path, ours.join("\n")).unwrap();
+        fs::write(&reference_path, reference.join("\n")).unwrap();
+        let divergence = diff_traces(
+            &ours_path.to_string_lossy(),
+            &reference_path.to_string_lossy(),
+            2,
+        );
+        _ = fs::remove_file(ours_path);
+        _ = fs::remove_file(reference_path);
+        divergence.unwrap()
+    }
+
+    // inc a on every line
+    fn trace(lines: usize) -> Vec<String> {
+        (0..lines)
+            .map(|i| line(0x100 + i as u16, i as u8))
+            .collect()
+    }
+
+    #[test]
+    fn identical() {
+        assert!(diff("identical", &trace(10), &trace(10)).is_none());
+    }
+
+    #[test]
+    fn diverges() {
+        let mut ours = trace(10);
+        ours[5] = line(0x105, 0x42);
+        let divergence = diff("diverges", &ours, &trace(10)).unwrap();
+        assert_eq!(divergence.line, 6);
+        assert_eq!(divergence.before, trace(10)[3..5]);
+        assert_eq!(divergence.ours_after, trace(10)[6..8]);
+        assert_eq!(
+            divergence.fields,
+            vec![("A".to_string(), "42".to_string(), "05".to_string())]
+        );
+        assert_eq!(
+            divergence.instruction.as_deref(),
+            Some("0104 inc a (3C,00,00,00)")
+        );
+
+        let report = divergence.report();
+        assert!(report.starts_with("First difference at line 6\n"));
+        assert!(report.contains("         4  A:03"));
+        assert!(report.contains("  A is 42, expected 05\n"));
+    }
+
+    #[test]
+    fn ends_early() {
+        let divergence = diff("ends-early", &trace(4), &trace(6)).unwrap();
+        assert_eq!(divergence.line, 5);
+        assert_eq!(divergence.ours, None);
+        assert_eq!(divergence.reference, Some(trace(6)[4].clone()));
+        assert!(divergence.fields.is_empty());
+        assert!(divergence.report().contains("<end of trace>  (ours)"));
+
+        let divergence = diff("ends-late", &trace(6), &trace(4)).unwrap();
+        assert_eq!(divergence.line, 5);
+        assert_eq!(divergence.reference, None);
+        assert_eq!(divergence.ours_after, trace(6)[5..]);
+    }
+}
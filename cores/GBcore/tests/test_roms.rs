@@ -0,0 +1,142 @@
+use std::path::PathBuf;
+
+use GBcore::cartridge::Cartridge;
+use GBcore::cpu::TestResult;
+use GBcore::model::Model;
+use GBcore::mooneye::{self, Outcome};
+use GBcore::GameBoy;
+
+// the test ROMs ship with the emulator at the repository root
+fn rom_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../../roms")
+        .join(name)
+}
+
+fn load(name: &str, model: Option<Model>) -> GameBoy {
+    let path = rom_path(name);
+    let cartridge = Cartridge::from_path(path.to_str().unwrap())
+        .unwrap_or_else(|err| panic!("Couldn't load {}: {}", name, err));
+    match model {
+        Some(model) => GameBoy::with_model(cartridge, model),
+        None => GameBoy::new(cartridge),
+    }
+}
+
+// Runs until the ROM reports its result over the serial port, or the frame
+// budget runs out. Returns everything it printed.
+fn run_serial(gameboy: &mut GameBoy, frame_budget: u32) -> String {
+    let mut text = String::new();
+    for _ in 0..frame_budget {
+        gameboy.run_frame().unwrap();
+        gameboy.audio_samples();
+        text.push_str(&String::from_utf8_lossy(&gameboy.serial_output()));
+        if text.contains("Passed") || text.contains("Failed") {
+            break;
+        }
+    }
+    text
+}
+
+fn assert_passed(name: &str, text: &str) {
+    assert!(
+        text.contains("Passed"),
+        "{} didn't pass, serial output:\n{}",
+        name,
+        text
+    );
+}
+
+// FNV-1a, stable across platforms and Rust versions unlike the std hashers
+fn frame_hash(gameboy: &GameBoy) -> u64 {
+    gameboy
+        .framebuffer()
+        .to_shades()
+        .iter()
+        .fold(0xCBF2_9CE4_8422_2325, |hash, &shade| {
+            (hash ^ shade as u64).wrapping_mul(0x0000_0100_0000_01B3)
+        })
+}
+
+#[test]
+fn cpu_instrs() {
+    let mut gameboy = load("cpu_instrs.gb", None);
+    let text = run_serial(&mut gameboy, 6000);
+    assert_passed("cpu_instrs", &text);
+}
+
+#[test]
+fn cpu_instrs_dmg() {
+    let mut gameboy = load("cpu_instrs.gb", Some(Model::DMG));
+    let text = run_serial(&mut gameboy, 6000);
+    assert_passed("cpu_instrs on DMG", &text);
+}
+
+#[test]
+fn mem_timing() {
+    let mut gameboy = load("mem_timing.gb", None);
+    let text = run_serial(&mut gameboy, 1000);
+    assert_passed("mem_timing", &text);
+}
+
+#[test]
+fn dmg_acid2() {
+    let mut gameboy = load("dmg-acid2.gb", None);
+    for _ in 0..120 {
+        gameboy.run_frame().unwrap();
+    }
+    // the 2-bit shades of a frame that matches the reference image
+    assert_eq!(frame_hash(&gameboy), 0xF272_A8FF_E3DB_4C16);
+}
+
+// A 32KB ROM that loads B-L and hits the LD B,B breakpoint, the way Mooneye
+// tests finish. Written to the temp dir since cartridges load from files.
+fn breakpoint_rom(name: &str, registers: [u8; 6]) -> PathBuf {
+    let mut rom = vec![0; 0x8000];
+    // NOP, JP 0x150
+    rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+    rom[0x134..0x134 + name.len()].copy_from_slice(name.as_bytes());
+    rom[0x14D] = (0x134..0x14D).fold(0u8, |sum, address| {
+        sum.wrapping_sub(rom[address]).wrapping_sub(1)
+    });
+    let mut code = Vec::new();
+    // LD B,n  LD C,n  LD D,n  LD E,n  LD H,n  LD L,n
+    for (opcode, value) in [0x06, 0x0E, 0x16, 0x1E, 0x26, 0x2E].iter().zip(registers) {
+        code.extend_from_slice(&[*opcode, value]);
+    }
+    // LD B,B, then JR to itself
+    code.extend_from_slice(&[0x40, 0x18, 0xFE]);
+    rom[0x150..0x150 + code.len()].copy_from_slice(&code);
+
+    let path = std::env::temp_dir().join(format!("rustboy-{}.gb", name));
+    std::fs::write(&path, rom).unwrap();
+    path
+}
+
+#[test]
+fn mooneye_breakpoint() {
+    let passing = breakpoint_rom("mooneye-pass", [3, 5, 8, 13, 21, 34]);
+    let failing = breakpoint_rom("mooneye-fail", [0x42; 6]);
+    assert_eq!(mooneye::run_rom(&passing), Outcome::Passed);
+    assert_eq!(mooneye::run_rom(&failing), Outcome::Failed);
+
+    // nothing is reported unless asked for
+    for detect in [false, true] {
+        let cartridge = Cartridge::from_path(passing.to_str().unwrap()).unwrap();
+        let mut gameboy = GameBoy::new(cartridge);
+        gameboy.detect_test_result(detect);
+        gameboy.run_frame().unwrap();
+        let expected = detect.then_some(TestResult::Passed);
+        assert_eq!(gameboy.test_result(), expected);
+    }
+}
+
+#[test]
+fn mooneye_models() {
+    assert_eq!(mooneye::model_for("boot_regs-dmg0"), Some(Model::DMG0));
+    assert_eq!(mooneye::model_for("boot_div-dmgABCmgb"), Some(Model::DMG));
+    assert_eq!(mooneye::model_for("boot_hwio-S"), Some(Model::SGB));
+    assert_eq!(mooneye::model_for("boot_regs-sgb2"), Some(Model::SGB2));
+    assert_eq!(mooneye::model_for("boot_div-cgbABCDE"), Some(Model::CGB));
+    assert_eq!(mooneye::model_for("add_sp_e_timing"), None);
+}
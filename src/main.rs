@@ -1,22 +1,203 @@
 use std::{env, path::Path};
-use GBcore::GBCore;
+use GBcore::{bgb::LinkTarget, disasm, model::Model, mooneye, trace, GBCore, SyncMode};
 // use crate::
 
-// #[macro_use]
-// extern crate lazy_static;
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        println!("Usage: program <path_to_rom>");
-        return;
+    let usage = "Usage: program [--sync timer|audio|vsync] [--model dmg0|dmg|mgb|sgb|sgb2|cgb|agb] [--boot-rom <path>] [--link <path_to_second_rom>] [--listen <address> | --connect <address>] [--serial-stdout | --debug] [--trace <path>] [--trace-start-pc <hex>] [--trace-start-cycle <n>] [--trace-limit <bytes>] [--doctor] [--sym <path>] <path_to_rom>\n       program --mooneye <path_to_test_roms>\n       program --trace-diff <our_trace> <reference_trace>\n       program --disasm <path_to_rom> <output> [<bank>|<first_bank>-<last_bank>]";
+
+    let mut emulator = GBCore::new();
+    let mut path = None;
+    let mut link_path = None;
+    let mut serial_stdout = false;
+    let mut debug = false;
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--sync" => {
+                i += 1;
+                match args.get(i).and_then(|name| SyncMode::from_name(name)) {
+                    Some(sync) => emulator.sync = sync,
+                    None => {
+                        println!("{}", usage);
+                        return;
+                    }
+                }
+            }
+            "--model" => {
+                i += 1;
+                match args.get(i).and_then(|name| Model::from_name(name)) {
+                    Some(model) => emulator.model = Some(model),
+                    None => {
+                        println!("{}", usage);
+                        return;
+                    }
+                }
+            }
+            "--link" => {
+                i += 1;
+                match args.get(i) {
+                    Some(second) => link_path = Some(second.clone()),
+                    None => {
+                        println!("{}", usage);
+                        return;
+                    }
+                }
+            }
+            "--serial-stdout" => serial_stdout = true,
+            "--debug" => debug = true,
+            "--doctor" => emulator.doctor = true,
+            "--sym" => {
+                i += 1;
+                match args.get(i) {
+                    Some(symbols) => emulator.symbols = Some(symbols.clone()),
+                    None => {
+                        println!("{}", usage);
+                        return;
+                    }
+                }
+            }
+            "--trace" => {
+                i += 1;
+                match args.get(i) {
+                    Some(trace) => emulator.trace = Some(trace.clone()),
+                    None => {
+                        println!("{}", usage);
+                        return;
+                    }
+                }
+            }
+            "--trace-start-pc" => {
+                i += 1;
+                let pc = args
+                    .get(i)
+                    .and_then(|pc| u16::from_str_radix(pc.trim_start_matches("0x"), 16).ok());
+                match pc {
+                    Some(pc) => emulator.trace_options.start_pc = Some(pc),
+                    None => {
+                        println!("{}", usage);
+                        return;
+                    }
+                }
+            }
+            "--trace-start-cycle" | "--trace-limit" => {
+                let option = args[i].clone();
+                i += 1;
+                match args.get(i).and_then(|value| value.parse::<u64>().ok()) {
+                    Some(value) if option == "--trace-limit" => {
+                        emulator.trace_options.max_bytes = Some(value)
+                    }
+                    Some(value) => emulator.trace_options.start_cycle = Some(value),
+                    None => {
+                        println!("{}", usage);
+                        return;
+                    }
+                }
+            }
+            "--trace-diff" => {
+                let (Some(ours), Some(reference)) = (args.get(i + 1), args.get(i + 2)) else {
+                    println!("{}", usage);
+                    return;
+                };
+                match trace::diff_traces(ours, reference, 5) {
+                    Ok(Some(divergence)) => print!("{}", divergence.report()),
+                    Ok(None) => println!("The traces match"),
+                    Err(err) => println!("{}", err),
+                }
+                return;
+            }
+            "--disasm" => {
+                let (Some(rom), Some(output)) = (args.get(i + 1), args.get(i + 2)) else {
+                    println!("{}", usage);
+                    return;
+                };
+                // banks are hex like everywhere else, 0-1F or a single one
+                let banks = match args.get(i + 3) {
+                    Some(range) => {
+                        let (first, last) = range.split_once('-').unwrap_or((range, range));
+                        match (
+                            usize::from_str_radix(first, 16),
+                            usize::from_str_radix(last, 16),
+                        ) {
+                            (Ok(first), Ok(last)) => Some((first, last)),
+                            _ => {
+                                println!("{}", usage);
+                                return;
+                            }
+                        }
+                    }
+                    None => None,
+                };
+                if let Err(err) = disasm::write_rom(rom, banks, output) {
+                    println!("{}", err);
+                }
+                return;
+            }
+            "--mooneye" => {
+                i += 1;
+                let Some(directory) = args.get(i) else {
+                    println!("{}", usage);
+                    return;
+                };
+                match mooneye::run_dir(Path::new(directory)) {
+                    Ok(reports) => mooneye::print_table(&reports),
+                    Err(err) => println!("{}", err),
+                }
+                return;
+            }
+            "--listen" | "--connect" => {
+                let listen = args[i] == "--listen";
+                i += 1;
+                match args.get(i) {
+                    Some(address) if listen => {
+                        emulator.remote_link = Some(LinkTarget::Listen(address.clone()))
+                    }
+                    Some(address) => {
+                        emulator.remote_link = Some(LinkTarget::Connect(address.clone()))
+                    }
+                    None => {
+                        println!("{}", usage);
+                        return;
+                    }
+                }
+            }
+            "--boot-rom" => {
+                i += 1;
+                match args.get(i) {
+                    Some(boot_rom) => emulator.boot_rom = Some(boot_rom.clone()),
+                    None => {
+                        println!("{}", usage);
+                        return;
+                    }
+                }
+            }
+            arg if path.is_none() && !arg.starts_with("--") => path = Some(arg.to_string()),
+            _ => {
+                println!("{}", usage);
+                return;
+            }
+        }
+        i += 1;
     }
-    let path = &args[1];
-    // let path = "/Users/fgoja/dev/rust/rustboy/roms/tetris.gb";
-    if !Path::new(path).exists() {
-        println!("File does not exist: {}", path);
+    let Some(path) = path else {
+        println!("{}", usage);
         return;
+    };
+    // let path = "/Users/fgoja/dev/rust/rustboy/roms/tetris.gb";
+    for path in [Some(&path), link_path.as_ref()].into_iter().flatten() {
+        if !Path::new(path).exists() {
+            println!("File does not exist: {}", path);
+            return;
+        }
     }
     // Initialize the emulator
-    let mut emulator = GBCore::new();
-    emulator.start(path);
+    let result = match &link_path {
+        Some(link_path) => emulator.start_linked(&path, link_path),
+        None if serial_stdout => emulator.run_serial_stdout(&path),
+        None if debug => emulator.run_debugger(&path),
+        None => emulator.start(&path),
+    };
+    if let Err(err) = result {
+        println!("{}", err);
+    }
 }